@@ -0,0 +1,414 @@
+use crate::{
+    color::{self, Color},
+    ray::Ray,
+    vector::Vector3,
+    Intersection,
+};
+
+/// Render pass of what the camera sees besides the color, ie. an arbitrary
+/// output variable.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aov {
+    /// Distance from the camera to the surface
+    Depth,
+    /// Normal of the surface in world space
+    Normal,
+    /// Color of the surface's material without any lighting
+    Albedo,
+    /// Point on the surface in world space
+    Position,
+    /// Number of the object in the scene's `objects` starting from one, or
+    /// zero where nothing is hit
+    ObjectId,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::Position,
+        Aov::ObjectId,
+    ];
+
+    /// Name of the pass used eg. in filenames.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::ObjectId => "id",
+        }
+    }
+
+    /// Names of the channels of the pass in an OpenEXR file following the
+    /// common naming of compositing software.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["N.X", "N.Y", "N.Z"],
+            Aov::Albedo => &["albedo.R", "albedo.G", "albedo.B"],
+            Aov::Position => &["P.X", "P.Y", "P.Z"],
+            Aov::ObjectId => &["id"],
+        }
+    }
+
+    /// Return the pass of `surfaces` as three floats per pixel. Passes of
+    /// one value repeat it in every channel.
+    pub fn linear_flat(&self, surfaces: &[Surface]) -> Vec<f32> {
+        surfaces
+            .iter()
+            .flat_map(|s| match self {
+                Aov::Depth => [s.depth; 3],
+                Aov::Normal => [s.normal.x, s.normal.y, s.normal.z],
+                Aov::Albedo => [s.albedo.r(), s.albedo.g(), s.albedo.b()],
+                Aov::Position => [s.position.x, s.position.y, s.position.z],
+                Aov::ObjectId => [s.object_id as f32; 3],
+            })
+            .collect()
+    }
+
+    /// Return the pass of `surfaces` as RGB-bytes for viewing. Depth and
+    /// position are scaled to fit the range of values in the image and the
+    /// objects get colors that are easy to tell apart. Where nothing is hit is
+    /// black.
+    pub fn rgb_flat(&self, surfaces: &[Surface]) -> Vec<u8> {
+        let byte = |x: f32| (x.clamp(0.0, 1.0) * (u8::MAX as f32 + 1.0)) as u8;
+        let hits = surfaces.iter().filter(|s| s.object_id > 0);
+        match self {
+            Aov::Depth => {
+                let near = hits.fold(f32::INFINITY, |near, s| near.min(s.depth));
+                surfaces
+                    .iter()
+                    .flat_map(|s| {
+                        // Inverse depth keeps detail close by even if some
+                        // surface like a plane goes very far
+                        let x = if s.object_id > 0 {
+                            byte(near.max(f32::EPSILON) / s.depth)
+                        } else {
+                            0
+                        };
+                        [x; 3]
+                    })
+                    .collect()
+            }
+            Aov::Normal => surfaces
+                .iter()
+                .flat_map(|s| {
+                    if s.object_id == 0 {
+                        return [0; 3];
+                    }
+                    let Vector3 { x, y, z } = s.normal;
+                    [
+                        byte(0.5 * x + 0.5),
+                        byte(0.5 * y + 0.5),
+                        byte(0.5 * z + 0.5),
+                    ]
+                })
+                .collect(),
+            Aov::Albedo => surfaces
+                .iter()
+                .flat_map(|s| <[u8; 3]>::from(s.albedo))
+                .collect(),
+            Aov::Position => {
+                let bounds = hits.map(|s| s.position).fold(None, |acc, p| {
+                    Some(match acc {
+                        Some((min, max)) => (
+                            componentwise(min, p, f32::min),
+                            componentwise(max, p, f32::max),
+                        ),
+                        None => (p, p),
+                    })
+                });
+                surfaces
+                    .iter()
+                    .flat_map(|s| match bounds {
+                        Some((min, max)) if s.object_id > 0 => {
+                            let scale = |x: f32, min: f32, max: f32| {
+                                byte((x - min) / (max - min).max(f32::EPSILON))
+                            };
+                            [
+                                scale(s.position.x, min.x, max.x),
+                                scale(s.position.y, min.y, max.y),
+                                scale(s.position.z, min.z, max.z),
+                            ]
+                        }
+                        _ => [0; 3],
+                    })
+                    .collect()
+            }
+            Aov::ObjectId => surfaces
+                .iter()
+                .flat_map(|s| {
+                    if s.object_id == 0 {
+                        return [0; 3];
+                    }
+                    // Scramble the bits so that consecutive numbers get
+                    // unrelated colors
+                    let hash = s.object_id.wrapping_mul(0x9e37_79b9).to_be_bytes();
+                    [hash[0], hash[1], hash[2]]
+                })
+                .collect(),
+        }
+    }
+}
+
+fn componentwise(a: Vector3, b: Vector3, f: fn(f32, f32) -> f32) -> Vector3 {
+    Vector3 {
+        x: f(a.x, b.x),
+        y: f(a.y, b.y),
+        z: f(a.z, b.z),
+    }
+}
+
+/// The surface seen through a pixel averaged over the samples that hit
+/// something.
+#[derive(Clone, Copy, Debug)]
+pub struct Surface {
+    /// Infinite where nothing is hit
+    pub depth: f32,
+    pub normal: Vector3,
+    pub albedo: Color,
+    pub position: Vector3,
+    /// Object hit by the first sample that hits any
+    pub object_id: u32,
+}
+
+impl Surface {
+    /// What is seen where nothing is hit
+    pub const NONE: Surface = Surface {
+        depth: f32::INFINITY,
+        normal: ZERO,
+        albedo: color::consts::BLACK,
+        position: ZERO,
+        object_id: 0,
+    };
+}
+
+const ZERO: Vector3 = Vector3 {
+    x: 0.0,
+    y: 0.0,
+    z: 0.0,
+};
+
+/// Sums of the surfaces hit by the samples of a pixel.
+#[derive(Clone, Copy)]
+pub(crate) struct SurfaceSum {
+    hits: usize,
+    depth: f32,
+    normal: Vector3,
+    albedo: Color,
+    position: Vector3,
+    object_id: u32,
+}
+
+impl Default for SurfaceSum {
+    fn default() -> Self {
+        SurfaceSum {
+            hits: 0,
+            depth: 0.0,
+            normal: ZERO,
+            albedo: color::consts::BLACK,
+            position: ZERO,
+            object_id: 0,
+        }
+    }
+}
+
+impl SurfaceSum {
+    /// Add the first `hit` of `ray` with the index of the object hit. The
+    /// point of the hit is in world space (see `Scene::first_hit`).
+    pub(crate) fn add(&mut self, ray: &Ray, hit: Option<(usize, Intersection)>) {
+        if let Some((index, intr)) = hit {
+            self.hits += 1;
+            self.depth += (intr.point - ray.origin).length();
+            self.normal = self.normal + intr.normal.into();
+            self.albedo += &intr.material.color;
+            self.position = self.position + intr.point;
+            if self.object_id == 0 {
+                self.object_id = index as u32 + 1;
+            }
+        }
+    }
+
+    pub(crate) fn surface(&self) -> Surface {
+        if self.hits == 0 {
+            return Surface::NONE;
+        }
+        let weight = 1.0 / self.hits as f32;
+        Surface {
+            depth: self.depth * weight,
+            // Normals pointing different ways average shorter
+            normal: if self.normal.length() > 0.0 {
+                self.normal.normalized().into()
+            } else {
+                ZERO
+            },
+            albedo: self.albedo * weight,
+            position: self.position * weight,
+            object_id: self.object_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scene::Scene, Material};
+    use std::convert::TryFrom;
+
+    fn vector(x: f32, y: f32, z: f32) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    fn ray_from(origin: Vector3) -> Ray {
+        Ray {
+            origin,
+            direction: vector(0.0, 0.0, -1.0).normalized(),
+            time: 0.0,
+        }
+    }
+
+    fn hit(point: Vector3, normal: Vector3, color: Color) -> Intersection {
+        Intersection {
+            t: 0.0,
+            incoming: vector(0.0, 0.0, -1.0).normalized(),
+            point,
+            normal: normal.normalized(),
+            material: Material {
+                color,
+                ..Material::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_surface_averages_hits() {
+        let ray = ray_from(vector(0.0, 0.0, 0.0));
+        let mut sum = SurfaceSum::default();
+        sum.add(
+            &ray,
+            Some((
+                2,
+                hit(
+                    vector(0.0, 0.0, -1.0),
+                    vector(1.0, 0.0, 0.0),
+                    Color::new(1.0, 0.0, 0.0),
+                ),
+            )),
+        );
+        // Misses are left out of the average
+        sum.add(&ray, None);
+        sum.add(
+            &ray,
+            Some((
+                0,
+                hit(
+                    vector(0.0, 0.0, -3.0),
+                    vector(0.0, 1.0, 0.0),
+                    Color::new(0.0, 0.0, 1.0),
+                ),
+            )),
+        );
+        let surface = sum.surface();
+
+        assert!((surface.depth - 2.0).abs() < 1e-6);
+        assert!((surface.position - vector(0.0, 0.0, -2.0)).length() < 1e-6);
+        assert!((surface.albedo.r() - 0.5).abs() < 1e-6 && (surface.albedo.b() - 0.5).abs() < 1e-6);
+        // The average of the normals is made unit length again
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((surface.normal - vector(half, half, 0.0)).length() < 1e-6);
+        // The id is of the first object hit
+        assert_eq!(surface.object_id, 3);
+    }
+
+    #[test]
+    fn test_surface_of_misses() {
+        let mut sum = SurfaceSum::default();
+        sum.add(&ray_from(vector(0.0, 0.0, 0.0)), None);
+        let surface = sum.surface();
+        assert_eq!(surface.object_id, Surface::NONE.object_id);
+        assert_eq!(surface.depth, f32::INFINITY);
+        assert_eq!(surface.normal.length(), 0.0);
+        assert_eq!(
+            surface.albedo.r() + surface.albedo.g() + surface.albedo.b(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_pass_layouts() {
+        let surfaces = [
+            Surface {
+                depth: 2.0,
+                normal: vector(1.0, 0.0, 0.0),
+                albedo: Color::new(1.0, 0.0, 0.0),
+                position: vector(1.0, 2.0, 3.0),
+                object_id: 4,
+            },
+            Surface::NONE,
+        ];
+
+        // Three floats per pixel in the order of the pixels
+        for &aov in Aov::ALL.iter() {
+            assert_eq!(aov.linear_flat(&surfaces).len(), 6);
+            assert_eq!(aov.rgb_flat(&surfaces).len(), 6);
+        }
+        assert_eq!(Aov::Depth.linear_flat(&surfaces)[..3], [2.0; 3]);
+        assert_eq!(Aov::Normal.linear_flat(&surfaces)[..3], [1.0, 0.0, 0.0]);
+        assert_eq!(Aov::Albedo.linear_flat(&surfaces)[..3], [1.0, 0.0, 0.0]);
+        assert_eq!(Aov::Position.linear_flat(&surfaces)[..3], [1.0, 2.0, 3.0]);
+        assert_eq!(
+            Aov::ObjectId.linear_flat(&surfaces),
+            [4.0, 4.0, 4.0, 0.0, 0.0, 0.0]
+        );
+
+        // The nearest hit is brightest and normals map from [-1, 1]
+        assert_eq!(Aov::Depth.rgb_flat(&surfaces)[..3], [255; 3]);
+        assert_eq!(Aov::Normal.rgb_flat(&surfaces)[..3], [255, 128, 128]);
+        assert_eq!(Aov::Albedo.rgb_flat(&surfaces)[..3], [255, 0, 0]);
+        // Nothing hit is black in every pass
+        for &aov in Aov::ALL.iter() {
+            assert_eq!(aov.rgb_flat(&surfaces)[3..], [0; 3], "{}", aov.name());
+        }
+    }
+
+    #[test]
+    fn test_first_hit_in_world_space() {
+        let scene = Scene::try_from(&mut serde_json::json!({
+            "fov": 90,
+            "ambient_color": [0, 0, 0],
+            "lights": [],
+            "named": {},
+            "objects": [
+                {
+                    "object": { "Sphere": { "origin": [3, 0, -10], "radius": 1 } }
+                },
+                {
+                    "transform": "Scale 2 2 2",
+                    "object": { "Sphere": { "origin": [0, 0, 0], "radius": 0.5 } }
+                }
+            ]
+        }))
+        .unwrap();
+
+        // The scaled sphere has a radius of 1 in world space
+        let ray = ray_from(vector(0.0, 0.0, 5.0));
+        let (index, intr) = scene.first_hit(&ray).unwrap();
+        assert_eq!(index, 1);
+        assert!((intr.point - vector(0.0, 0.0, 1.0)).length() < 1e-4);
+        let mut sum = SurfaceSum::default();
+        sum.add(&ray, Some((index, intr)));
+        let surface = sum.surface();
+        assert!((surface.depth - 4.0).abs() < 1e-4);
+        assert_eq!(surface.object_id, 2);
+
+        let ray = ray_from(vector(3.0, 0.0, 5.0));
+        let (index, intr) = scene.first_hit(&ray).unwrap();
+        assert_eq!(index, 0);
+        assert!((intr.t - 14.0).abs() < 1e-4);
+
+        assert!(scene.first_hit(&ray_from(vector(0.0, 5.0, 5.0))).is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    matrix::SquareMatrix4,
+    vector::{Vector3, Vector4},
+};
+
+/// Axis-aligned box that contains some set of points.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingBox {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl BoundingBox {
+    /// Return the smallest box containing all of `points` or `None` if there
+    /// are none.
+    pub fn from_points(points: impl IntoIterator<Item = Vector3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(
+            BoundingBox {
+                min: first,
+                max: first,
+            },
+            |acc, p| acc.union(&BoundingBox { min: p, max: p }),
+        ))
+    }
+
+    /// Return the smallest box containing both `self` and `other`.
+    pub fn union(&self, other: &BoundingBox) -> Self {
+        BoundingBox {
+            min: Vector3 {
+                x: self.min.x.min(other.min.x),
+                y: self.min.y.min(other.min.y),
+                z: self.min.z.min(other.min.z),
+            },
+            max: Vector3 {
+                x: self.max.x.max(other.max.x),
+                y: self.max.y.max(other.max.y),
+                z: self.max.z.max(other.max.z),
+            },
+        }
+    }
+
+    /// Return the box containing this box after it is transformed by `m`.
+    pub(crate) fn transformed(&self, m: &SquareMatrix4) -> Self {
+        let corners = (0..8).map(|i| {
+            let corner = Vector3 {
+                x: if i & 1 == 0 { self.min.x } else { self.max.x },
+                y: if i & 2 == 0 { self.min.y } else { self.max.y },
+                z: if i & 4 == 0 { self.min.z } else { self.max.z },
+            };
+            (m * &Vector4::from_v3(corner, 1.0)).xyz()
+        });
+        BoundingBox::from_points(corners).unwrap()
+    }
+
+    pub fn center(&self) -> Vector3 {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Radius of the smallest sphere around `center` that contains the box.
+    pub fn radius(&self) -> f32 {
+        0.5 * (self.max - self.min).length()
+    }
+}
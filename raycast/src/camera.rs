@@ -1,8 +1,266 @@
+use std::convert::TryFrom;
+use std::f32::consts::PI;
+
+use rand::{Rng, RngCore};
+
 use crate::{
+    bounds::BoundingBox,
+    film::{Exposure, ToneMapping},
     ray::Ray,
+    stereo::Stereo,
     vector::{UnitVector3, Vector3},
 };
 
+/// The `camera` section of a scene file.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct CameraDescription {
+    #[serde(default = "default_position")]
+    pub position: Vector3,
+    #[serde(default = "default_look_at")]
+    pub look_at: Vector3,
+    /// Direction that is approximately up in the image
+    #[serde(default = "default_up")]
+    pub up: Vector3,
+    /// Vertical field of view in degrees
+    #[serde(default = "default_fov")]
+    pub fov: f32,
+    #[serde(default)]
+    pub projection: Projection,
+    /// Radius of the lens opening. Zero makes a pinhole camera with
+    /// everything in focus. Only affects perspective cameras.
+    #[serde(default)]
+    pub aperture: f32,
+    /// Distance from the camera to the plane in focus, by default the distance
+    /// to `look_at`
+    #[serde(default)]
+    pub focus_distance: Option<f32>,
+    /// Amount of aperture blades to shape the out-of-focus blur like a
+    /// polygon instead of a disc
+    #[serde(default)]
+    pub blades: Option<u32>,
+    /// Horizontal shift of the image plane in halves of the image height, to
+    /// move the view sideways without turning the camera. Only affects
+    /// perspective cameras.
+    #[serde(default)]
+    pub shift: f32,
+    /// Render a view for each eye instead of a single image
+    #[serde(default)]
+    pub stereo: Option<Stereo>,
+    /// Exposure of the image. By default the radiance is used as is.
+    #[serde(default)]
+    pub exposure: Option<Exposure>,
+    /// Color temperature in Kelvin of the light that should look white
+    #[serde(default)]
+    pub white_balance: Option<f32>,
+    /// Curve that fits the radiance into the range of an 8-bit image
+    #[serde(default)]
+    pub tone_mapping: ToneMapping,
+}
+
+/// Select how the scene is projected onto the image plane.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub enum Projection {
+    /// Rays spread out from the camera position according to `fov`.
+    #[default]
+    Perspective,
+    /// Rays are parallel and cover a view of `height` units tall.
+    Orthographic { height: f32 },
+    /// Rays cover every direction with longitude along the image width and
+    /// latitude along its height. Best rendered into a 2:1 image.
+    Equirectangular,
+    /// Rays spread out evenly by angle into a circle that fits the image and
+    /// covers a field of view of `fov` degrees across.
+    Fisheye { fov: f32 },
+}
+
+impl CameraDescription {
+    /// Description matching `PerspectiveCamera::with_view` with `fov` given in
+    /// degrees.
+    pub fn with_fov(fov: f32) -> Self {
+        CameraDescription {
+            position: default_position(),
+            look_at: default_look_at(),
+            up: default_up(),
+            fov,
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: None,
+            blades: None,
+            shift: 0.0,
+            stereo: None,
+            exposure: None,
+            white_balance: None,
+            tone_mapping: ToneMapping::default(),
+        }
+    }
+
+    /// Return a copy of the camera moved so that everything in `bounds` fits
+    /// into an image of `aspect_ratio` with some margin around it. The camera
+    /// keeps its viewing direction but looks at the center of `bounds`.
+    ///
+    /// # Panics:
+    /// This panics if `position` and `look_at` are the same.
+    pub fn framed(&self, bounds: &BoundingBox, aspect_ratio: f32) -> Self {
+        let direction = UnitVector3::try_from(self.look_at - self.position)
+            .expect("Camera position and the point it looks at are the same");
+        // Fit the sphere around the box into the view
+        let radius = bounds.radius().max(f32::EPSILON) * FRAMING_MARGIN;
+
+        let (distance, projection) = match self.projection {
+            Projection::Perspective => {
+                let vertical = utils::degs_to_rads(self.fov) / 2.0;
+                let horizontal = (vertical.tan() * aspect_ratio).atan();
+                (radius / vertical.min(horizontal).sin(), self.projection)
+            }
+            Projection::Orthographic { .. } => (
+                2.0 * radius,
+                // Fit the shorter side of the image around the sphere
+                Projection::Orthographic {
+                    height: 2.0 * radius / aspect_ratio.min(1.0),
+                },
+            ),
+            Projection::Fisheye { fov } => {
+                let half_fov = utils::degs_to_rads(fov / 2.0).min(PI / 2.0);
+                (radius / half_fov.sin(), self.projection)
+            }
+            // Everything is seen from anywhere
+            Projection::Equirectangular => (2.0 * radius, self.projection),
+        };
+
+        let look_at = bounds.center();
+        CameraDescription {
+            position: look_at - direction * distance,
+            look_at,
+            projection,
+            focus_distance: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// How much larger than the framed objects the view is made
+const FRAMING_MARGIN: f32 = 1.1;
+
+fn default_position() -> Vector3 {
+    Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    }
+}
+
+fn default_look_at() -> Vector3 {
+    Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    }
+}
+
+fn default_up() -> Vector3 {
+    Vector3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    }
+}
+
+fn default_fov() -> f32 {
+    90.0
+}
+
+/// Projection of points on the image plane into rays shot into the scene.
+///
+/// Implement this to render with a projection of your own:
+/// ```
+/// use rand::RngCore;
+/// use raycast::{camera::Camera, ray::Ray, vector::Vector3};
+///
+/// /// Looks along the negative z-axis through a pinhole at the origin
+/// struct Pinhole(usize, usize);
+///
+/// impl Camera for Pinhole {
+///     fn image_dimensions(&self) -> (usize, usize) {
+///         (self.0, self.1)
+///     }
+///
+///     fn shoot_at(&self, x: f32, y: f32, _rng: &mut dyn RngCore) -> Option<Ray> {
+///         let origin = Vector3 { x: 0.0, y: 0.0, z: 0.0 };
+///         let direction = Vector3 { x, y, z: -1.0 }.normalized();
+///         // Everything is captured at the moment the shutter opens
+///         Some(Ray { origin, direction, time: 0.0 })
+///     }
+/// }
+/// ```
+pub trait Camera: Send + Sync {
+    /// Return the width and height of the rendered image in pixels.
+    fn image_dimensions(&self) -> (usize, usize);
+
+    /// Generate a ray through the image plane coordinates `x` and `y` that are
+    /// in [-1, 1]. Return `None` if the point is not covered by the
+    /// projection, eg. outside the circle of a fisheye image. For motion blur
+    /// the ray's `time` should be spread over the shutter interval [0, 1].
+    /// Random numbers are taken from `rng` so that renders can be repeated.
+    fn shoot_at(&self, x: f32, y: f32, rng: &mut dyn RngCore) -> Option<Ray>;
+}
+
+/// Constructs the camera selected by the `camera` section of a scene file.
+pub fn from_description(
+    description: &CameraDescription,
+    image_width: usize,
+    image_height: usize,
+) -> Box<dyn Camera> {
+    match description.projection {
+        Projection::Perspective => Box::new(PerspectiveCamera::from_description(
+            description,
+            image_width,
+            image_height,
+        )),
+        Projection::Orthographic { height } => Box::new(OrthographicCamera::looking_at(
+            description.position,
+            description.look_at,
+            description.up,
+            height,
+            image_width,
+            image_height,
+        )),
+        Projection::Equirectangular => Box::new(EquirectangularCamera::looking_at(
+            description.position,
+            description.look_at,
+            description.up,
+            image_width,
+            image_height,
+        )),
+        Projection::Fisheye { fov } => Box::new(FisheyeCamera::looking_at(
+            description.position,
+            description.look_at,
+            description.up,
+            utils::degs_to_rads(fov),
+            image_width,
+            image_height,
+        )),
+    }
+}
+
+/// Return the camera's horizontal, up and viewing directions.
+///
+/// # Panics:
+/// This panics if `position` and `look_at` are the same or if `up` is
+/// parallel to the viewing direction.
+pub(crate) fn basis(
+    position: Vector3,
+    look_at: Vector3,
+    up: Vector3,
+) -> (UnitVector3, UnitVector3, UnitVector3) {
+    let direction = UnitVector3::try_from(look_at - position)
+        .expect("Camera position and the point it looks at are the same");
+    let horizontal = UnitVector3::try_from(Vector3::from(direction).cross(&up))
+        .expect("Camera up-vector is parallel to the viewing direction");
+    // Make up perpendicular to the others
+    let up = horizontal.cross(&direction);
+    (horizontal, up, direction)
+}
+
 #[derive(Debug)]
 pub struct PerspectiveCamera {
     horizontal: UnitVector3,
@@ -13,6 +271,10 @@ pub struct PerspectiveCamera {
     aspect_ratio: f32,
     image_width: usize,
     image_height: usize,
+    aperture: f32,
+    focus_distance: f32,
+    blades: Option<u32>,
+    shift: f32,
 }
 impl PerspectiveCamera {
     /// Constructs a "normalized"(?) camera, meaning it points along the
@@ -22,29 +284,53 @@ impl PerspectiveCamera {
     /// A custom field of view `fov` is given in radians and the image plane's
     /// aspect ratio is calculated from `image_width` and `image_height`.
     pub fn with_view(fov: f32, image_width: usize, image_height: usize) -> Self {
-        let horizontal = Vector3 {
-            x: 1.0,
-            y: 0.0,
-            z: 0.0,
-        }
-        .normalized();
-        let up = Vector3 {
-            x: 0.0,
-            y: 1.0,
-            z: 0.0,
-        }
-        .normalized();
-        let direction = Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: -1.0,
-        }
-        .normalized();
-        let position = Vector3 {
-            x: 0.0,
-            y: 0.0,
-            z: 1.0,
-        };
+        Self::looking_at(
+            default_position(),
+            default_look_at(),
+            default_up(),
+            fov,
+            image_width,
+            image_height,
+        )
+    }
+
+    /// Constructs a camera from the `camera` section of a scene file.
+    pub fn from_description(
+        description: &CameraDescription,
+        image_width: usize,
+        image_height: usize,
+    ) -> Self {
+        let focus_distance = description
+            .focus_distance
+            .unwrap_or_else(|| (description.look_at - description.position).length());
+
+        Self::looking_at(
+            description.position,
+            description.look_at,
+            description.up,
+            utils::degs_to_rads(description.fov),
+            image_width,
+            image_height,
+        )
+        .with_lens(description.aperture, focus_distance, description.blades)
+        .with_shift(description.shift)
+    }
+
+    /// Constructs a camera at `position` pointing towards `look_at` and
+    /// rotated so that `up` points upwards in the image.
+    ///
+    /// # Panics:
+    /// This panics if `position` and `look_at` are the same or if `up` is
+    /// parallel to the viewing direction.
+    pub fn looking_at(
+        position: Vector3,
+        look_at: Vector3,
+        up: Vector3,
+        fov: f32,
+        image_width: usize,
+        image_height: usize,
+    ) -> Self {
+        let (horizontal, up, direction) = basis(position, look_at, up);
 
         // NOTE This assumes that x and y on image plane will be in [-1, 1]
         let dist_to_image = 1.0 / f32::tan(fov / 2.0);
@@ -60,23 +346,510 @@ impl PerspectiveCamera {
             aspect_ratio,
             image_width,
             image_height,
+            aperture: 0.0,
+            focus_distance: 1.0,
+            blades: None,
+            shift: 0.0,
         }
     }
 
-    pub fn image_dimensions(&self) -> (usize, usize) {
+    /// Replace the pinhole with a thin lens of radius `aperture` that is
+    /// focused at `focus_distance`. Optionally the lens is shaped like a
+    /// polygon with as many corners as there are `blades`.
+    pub fn with_lens(mut self, aperture: f32, focus_distance: f32, blades: Option<u32>) -> Self {
+        self.aperture = aperture;
+        self.focus_distance = focus_distance;
+        self.blades = blades;
+        self
+    }
+
+    /// Move the image plane `shift` halves of the image height to the right
+    /// while keeping the viewing direction.
+    pub fn with_shift(mut self, shift: f32) -> Self {
+        self.shift = shift;
+        self
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn image_dimensions(&self) -> (usize, usize) {
         (self.image_width, self.image_height)
     }
 
-    pub fn shoot_at(&self, x: f32, y: f32) -> Ray {
+    fn shoot_at(&self, x: f32, y: f32, rng: &mut dyn RngCore) -> Option<Ray> {
         // Generate ray from camera to the image plane
-        let ray_direction = x * self.horizontal * self.aspect_ratio
+        let ray_direction = ((x * self.aspect_ratio + self.shift) * self.horizontal
             + y * self.up
-            + self.dist_to_image * self.direction
-            - self.position;
+            + self.dist_to_image * self.direction)
+            .normalized();
+
+        if self.aperture <= 0.0 {
+            return Some(Ray {
+                origin: self.position,
+                direction: ray_direction,
+                time: rng.gen::<f32>(),
+            });
+        }
+
+        // Depth of field: Rays from anywhere on the lens meet at the plane of
+        // focus, so only things on that plane are sharp
+        let focus_point = self.position
+            + ray_direction * (self.focus_distance / ray_direction.dot(&self.direction));
+        let (lx, ly) = sample_lens(self.blades, rng);
+        let origin =
+            self.position + (self.aperture * lx) * self.horizontal + (self.aperture * ly) * self.up;
+
+        Some(Ray {
+            origin,
+            direction: (focus_point - origin).normalized(),
+            time: rng.gen::<f32>(),
+        })
+    }
+}
 
-        Ray {
+/// Return a random point on a unit disc or on a regular polygon with `blades`
+/// corners that fits inside the unit disc.
+fn sample_lens(blades: Option<u32>, rng: &mut dyn RngCore) -> (f32, f32) {
+    match blades {
+        Some(n) if n >= 3 => {
+            // Pick one of the triangles between the center and the polygon's
+            // edges and then a point on it
+            let i = (rng.gen::<f32>() * n as f32) as u32 % n;
+            let (a0, a1) = (
+                2.0 * PI * i as f32 / n as f32,
+                2.0 * PI * (i + 1) as f32 / n as f32,
+            );
+            let (mut s, mut t) = (rng.gen::<f32>(), rng.gen::<f32>());
+            if s + t > 1.0 {
+                // Fold the point back inside the triangle
+                s = 1.0 - s;
+                t = 1.0 - t;
+            }
+            (s * a0.cos() + t * a1.cos(), s * a0.sin() + t * a1.sin())
+        }
+        _ => {
+            // Square root spreads the points evenly by area
+            let r = rng.gen::<f32>().sqrt();
+            let phi = 2.0 * PI * rng.gen::<f32>();
+            (r * phi.cos(), r * phi.sin())
+        }
+    }
+}
+
+/// Camera with parallel rays, so that objects keep their size regardless of
+/// their distance.
+#[derive(Debug)]
+pub struct OrthographicCamera {
+    horizontal: UnitVector3,
+    up: UnitVector3,
+    direction: UnitVector3,
+    position: Vector3,
+    half_height: f32,
+    aspect_ratio: f32,
+    image_width: usize,
+    image_height: usize,
+}
+
+impl OrthographicCamera {
+    /// Constructs a camera centered at `position` pointing towards `look_at`
+    /// and seeing an area `view_height` units tall.
+    ///
+    /// # Panics:
+    /// This panics if `position` and `look_at` are the same or if `up` is
+    /// parallel to the viewing direction.
+    pub fn looking_at(
+        position: Vector3,
+        look_at: Vector3,
+        up: Vector3,
+        view_height: f32,
+        image_width: usize,
+        image_height: usize,
+    ) -> Self {
+        let (horizontal, up, direction) = basis(position, look_at, up);
+
+        OrthographicCamera {
+            horizontal,
+            up,
+            direction,
+            position,
+            half_height: view_height / 2.0,
+            aspect_ratio: image_width as f32 / image_height as f32,
+            image_width,
+            image_height,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn image_dimensions(&self) -> (usize, usize) {
+        (self.image_width, self.image_height)
+    }
+
+    fn shoot_at(&self, x: f32, y: f32, rng: &mut dyn RngCore) -> Option<Ray> {
+        // Move the origin on the camera's plane and shoot straight ahead
+        let origin = self.position
+            + x * self.half_height * self.aspect_ratio * self.horizontal
+            + y * self.half_height * self.up;
+
+        Some(Ray {
+            origin,
+            direction: self.direction,
+            time: rng.gen::<f32>(),
+        })
+    }
+}
+
+/// Camera that sees all around it, for rendering environment maps and
+/// panoramas.
+#[derive(Debug)]
+pub struct EquirectangularCamera {
+    horizontal: UnitVector3,
+    up: UnitVector3,
+    direction: UnitVector3,
+    position: Vector3,
+    image_width: usize,
+    image_height: usize,
+}
+
+impl EquirectangularCamera {
+    /// Constructs a camera at `position` with `look_at` in the middle of the
+    /// image.
+    ///
+    /// # Panics:
+    /// This panics if `position` and `look_at` are the same or if `up` is
+    /// parallel to the viewing direction.
+    pub fn looking_at(
+        position: Vector3,
+        look_at: Vector3,
+        up: Vector3,
+        image_width: usize,
+        image_height: usize,
+    ) -> Self {
+        let (horizontal, up, direction) = basis(position, look_at, up);
+
+        EquirectangularCamera {
+            horizontal,
+            up,
+            direction,
+            position,
+            image_width,
+            image_height,
+        }
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn image_dimensions(&self) -> (usize, usize) {
+        (self.image_width, self.image_height)
+    }
+
+    fn shoot_at(&self, x: f32, y: f32, rng: &mut dyn RngCore) -> Option<Ray> {
+        // Image edges are directly behind the camera and the top and bottom
+        // are straight up and down
+        let longitude = x * PI;
+        let latitude = y * PI / 2.0;
+        let ray_direction = (latitude.cos() * longitude.sin()) * self.horizontal
+            + latitude.sin() * self.up
+            + (latitude.cos() * longitude.cos()) * self.direction;
+
+        Some(Ray {
             origin: self.position,
             direction: ray_direction.normalized(),
+            time: rng.gen::<f32>(),
+        })
+    }
+}
+
+/// Camera with an equidistant fisheye lens that makes a circular image.
+#[derive(Debug)]
+pub struct FisheyeCamera {
+    horizontal: UnitVector3,
+    up: UnitVector3,
+    direction: UnitVector3,
+    position: Vector3,
+    fov: f32,
+    aspect_ratio: f32,
+    image_width: usize,
+    image_height: usize,
+}
+
+impl FisheyeCamera {
+    /// Constructs a camera at `position` pointing towards `look_at` and
+    /// covering the field of view `fov` (in radians) across the image circle.
+    ///
+    /// # Panics:
+    /// This panics if `position` and `look_at` are the same or if `up` is
+    /// parallel to the viewing direction.
+    pub fn looking_at(
+        position: Vector3,
+        look_at: Vector3,
+        up: Vector3,
+        fov: f32,
+        image_width: usize,
+        image_height: usize,
+    ) -> Self {
+        let (horizontal, up, direction) = basis(position, look_at, up);
+
+        FisheyeCamera {
+            horizontal,
+            up,
+            direction,
+            position,
+            fov,
+            aspect_ratio: image_width as f32 / image_height as f32,
+            image_width,
+            image_height,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn image_dimensions(&self) -> (usize, usize) {
+        (self.image_width, self.image_height)
+    }
+
+    fn shoot_at(&self, x: f32, y: f32, rng: &mut dyn RngCore) -> Option<Ray> {
+        // Fit the circle into the shorter side of the image
+        let (px, py) = (x * self.aspect_ratio, y);
+        let r = (px * px + py * py).sqrt() / self.aspect_ratio.min(1.0);
+        if r > 1.0 {
+            return None;
+        }
+
+        // Distance from the center is proportional to angle from the
+        // viewing direction
+        let theta = r * self.fov / 2.0;
+        let psi = py.atan2(px);
+        let ray_direction = (theta.sin() * psi.cos()) * self.horizontal
+            + (theta.sin() * psi.sin()) * self.up
+            + theta.cos() * self.direction;
+
+        Some(Ray {
+            origin: self.position,
+            direction: ray_direction.normalized(),
+            time: rng.gen::<f32>(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basis_from_position_look_at_up() {
+        let (horizontal, up, direction) = basis(
+            Vector3 {
+                x: 2.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            Vector3 {
+                x: 3.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            Vector3 {
+                x: 0.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        );
+        let s = 1.0 / 2f32.sqrt();
+        assert!((direction.x() - 1.0).abs() < 1e-6);
+        // Up is tilted only as much as needed to be perpendicular
+        assert!(horizontal.x().abs() < 1e-6);
+        assert!((horizontal.y() - -s).abs() < 1e-6 && (horizontal.z() - s).abs() < 1e-6);
+        assert!(up.x().abs() < 1e-6);
+        assert!((up.y() - s).abs() < 1e-6 && (up.z() - s).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fov_spans_image_away_from_origin() {
+        let rng = &mut rand::thread_rng();
+        let camera = PerspectiveCamera::looking_at(
+            Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 5.0,
+            },
+            default_look_at(),
+            default_up(),
+            PI / 2.0,
+            4,
+            4,
+        );
+        // The edge of the image is half of the field of view off center
+        // wherever the camera is
+        let top = camera.shoot_at(0.0, 1.0, rng).unwrap().direction;
+        assert!((top.y() - -top.z()).abs() < 1e-6 && top.y() > 0.0);
+    }
+
+    #[test]
+    fn test_lens_samples_inside_disc() {
+        let rng = &mut rand::thread_rng();
+        for _ in 0..1000 {
+            let (x, y) = sample_lens(None, rng);
+            assert!(x * x + y * y <= 1.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_lens_samples_inside_square() {
+        let rng = &mut rand::thread_rng();
+        // Four blades make a square with corners on the axes
+        for _ in 0..1000 {
+            let (x, y) = sample_lens(Some(4), rng);
+            assert!(x.abs() + y.abs() <= 1.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_lens_keeps_focus_plane_sharp() {
+        let rng = &mut rand::thread_rng();
+        let camera = PerspectiveCamera::with_view(PI / 2.0, 4, 4).with_lens(0.5, 3.0, None);
+        let pinhole = PerspectiveCamera::with_view(PI / 2.0, 4, 4);
+        let focus = {
+            let ray = pinhole.shoot_at(0.3, -0.2, rng).unwrap();
+            ray.cast(3.0 / ray.direction.dot(&pinhole.direction))
+        };
+        for _ in 0..100 {
+            let ray = camera.shoot_at(0.3, -0.2, rng).unwrap();
+            // Every ray passes through the same point on the plane of focus
+            let hit = ray.cast((focus.z - ray.origin.z) / ray.direction.z());
+            assert!((hit - focus).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_orthographic_rays_are_parallel() {
+        let rng = &mut rand::thread_rng();
+        let camera = OrthographicCamera::looking_at(
+            Vector3 {
+                x: 1.0,
+                y: 0.0,
+                z: 5.0,
+            },
+            Vector3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            default_up(),
+            2.0,
+            8,
+            4,
+        );
+        let bottom_left = camera.shoot_at(-1.0, -1.0, rng).unwrap();
+        let top_right = camera.shoot_at(1.0, 1.0, rng).unwrap();
+        let center = camera.shoot_at(0.0, 0.0, rng).unwrap();
+        for ray in &[&bottom_left, &top_right, &center] {
+            assert!((ray.direction.z() - -1.0).abs() < 1e-6);
+            assert!((ray.origin.z - 5.0).abs() < 1e-6);
+        }
+        assert!((center.origin.x - 1.0).abs() < 1e-6 && center.origin.y.abs() < 1e-6);
+        // The origins cover the view height and the image is twice as wide
+        let span = top_right.origin - bottom_left.origin;
+        assert!((span.y - 2.0).abs() < 1e-6);
+        assert!((span.x - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_equirectangular_directions() {
+        let rng = &mut rand::thread_rng();
+        let camera = EquirectangularCamera::looking_at(
+            default_position(),
+            default_look_at(),
+            default_up(),
+            8,
+            4,
+        );
+        let forward = camera.shoot_at(0.0, 0.0, rng).unwrap().direction;
+        assert!((forward.z() - -1.0).abs() < 1e-6);
+        let right = camera.shoot_at(0.5, 0.0, rng).unwrap().direction;
+        assert!((right.x() - 1.0).abs() < 1e-6);
+        let behind = camera.shoot_at(1.0, 0.0, rng).unwrap().direction;
+        assert!((behind.z() - 1.0).abs() < 1e-6);
+        let up = camera.shoot_at(0.3, 1.0, rng).unwrap().direction;
+        assert!((up.y() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fisheye_circle() {
+        let rng = &mut rand::thread_rng();
+        let camera = FisheyeCamera::looking_at(
+            default_position(),
+            default_look_at(),
+            default_up(),
+            PI,
+            8,
+            4,
+        );
+        // Image is twice as wide, so the circle's edge is at x = 0.5
+        let side = camera.shoot_at(0.5, 0.0, rng).unwrap().direction;
+        assert!((side.x() - 1.0).abs() < 1e-6);
+        assert!(camera.shoot_at(0.6, 0.0, rng).is_none());
+        assert!(camera.shoot_at(0.0, 1.01, rng).is_none());
+    }
+
+    #[test]
+    fn test_framing_fits_bounds() {
+        let bounds = BoundingBox {
+            min: Vector3 {
+                x: 2.0,
+                y: -1.0,
+                z: -6.0,
+            },
+            max: Vector3 {
+                x: 4.0,
+                y: 1.0,
+                z: -4.0,
+            },
+        };
+        let description = CameraDescription::with_fov(60.0).framed(&bounds, 2.0);
+        let camera = PerspectiveCamera::from_description(&description, 8, 4);
+
+        // The viewing direction is kept
+        let center = camera.shoot_at(0.0, 0.0, &mut rand::thread_rng()).unwrap();
+        assert!((center.direction.z() - -1.0).abs() < 1e-6);
+        assert!((center.origin.x - 3.0).abs() < 1e-5 && center.origin.y.abs() < 1e-5);
+
+        // Every corner is inside the view
+        for &(x, y, z) in &[(2.0, -1.0, -4.0), (4.0, 1.0, -4.0), (2.0, 1.0, -6.0)] {
+            let to_corner = Vector3 { x, y, z } - description.position;
+            let depth = -to_corner.z;
+            let half_height = depth * utils::degs_to_rads(30.0).tan();
+            assert!(to_corner.y.abs() < half_height);
+            assert!(to_corner.x.abs() < 2.0 * half_height);
+        }
+    }
+
+    #[test]
+    fn test_framing_sets_orthographic_height() {
+        let bounds = BoundingBox {
+            min: Vector3 {
+                x: -1.0,
+                y: -2.0,
+                z: -2.0,
+            },
+            max: Vector3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        };
+        let description = CameraDescription {
+            projection: Projection::Orthographic { height: 1.0 },
+            ..CameraDescription::with_fov(60.0)
+        };
+        let radius = 3f32.sqrt() * FRAMING_MARGIN;
+        for &(aspect_ratio, height) in &[(2.0, 2.0 * radius), (0.5, 4.0 * radius)] {
+            let framed = description.framed(&bounds, aspect_ratio);
+            match framed.projection {
+                Projection::Orthographic { height: h } => assert!((h - height).abs() < 1e-5),
+                p => panic!("Framing changed the projection to {:?}", p),
+            }
+            assert!((framed.look_at - bounds.center()).length() < 1e-6);
         }
     }
 }
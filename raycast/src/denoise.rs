@@ -0,0 +1,278 @@
+use crate::{
+    aov::Surface,
+    color::{self, Color},
+    raycaster::Frame,
+};
+
+/// Weights of the pixels along one axis of the filter, ie. the B3 spline
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Edge-avoiding à-trous wavelet filter from "Edge-Avoiding À-Trous Wavelet
+/// Transform for fast Global Illumination Filtering" by Dammertz et al. Each
+/// iteration blurs with gaps twice as wide as the previous one, and pixels
+/// only blur together if their normal, albedo and brightness are close. As in
+/// "Spatiotemporal Variance-Guided Filtering" by Schied et al. brightness is
+/// compared relative to the noise left in the pixel so that pixels without
+/// noise stay sharp.
+#[derive(Clone, Copy, Debug)]
+pub struct Denoiser {
+    /// Amount of times to filter; the blur reaches `2^iterations` pixels
+    pub iterations: usize,
+    /// How many standard deviations of noise apart brightnesses still blur
+    /// together
+    pub luminance_sigma: f32,
+    /// How different albedos still blur together
+    pub albedo_sigma: f32,
+    /// How sharply differences in the normals stop the blur
+    pub normal_exponent: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            luminance_sigma: 4.0,
+            albedo_sigma: 0.1,
+            normal_exponent: 128.0,
+        }
+    }
+}
+
+/// Amount of noise under which brightnesses have to match exactly to blur
+const MIN_DEVIATION: f32 = 1e-6;
+
+impl Denoiser {
+    /// Return the colors of `frame` with the noise smoothed out.
+    ///
+    /// # Panics:
+    /// This panics if the frame is rendered without `aovs` that guide the
+    /// filter
+    pub fn denoise(&self, frame: &Frame) -> Vec<Color> {
+        let surfaces = frame
+            .surfaces
+            .as_ref()
+            .expect("Denoising needs the AOVs rendered");
+        let mut colors = frame.colors.clone();
+        let mut variances = estimate_variances(frame);
+        for i in 0..self.iterations {
+            let (c, v) = self.filter(
+                frame.width,
+                frame.height,
+                &colors,
+                &variances,
+                surfaces,
+                1 << i,
+            );
+            colors = c;
+            variances = v;
+        }
+        colors
+    }
+
+    /// Blur once with `step` pixels between the taps of the kernel. Return
+    /// the blurred colors and their variances.
+    fn filter(
+        &self,
+        width: usize,
+        height: usize,
+        colors: &[Color],
+        variances: &[f32],
+        surfaces: &[Surface],
+        step: usize,
+    ) -> (Vec<Color>, Vec<f32>) {
+        let mut filtered = Vec::with_capacity(colors.len());
+        let mut filtered_variances = Vec::with_capacity(colors.len());
+        for y in 0..height {
+            for x in 0..width {
+                let p = y * width + x;
+                let deviation = self.luminance_sigma * variances[p].sqrt() + MIN_DEVIATION;
+                let mut sum = color::consts::BLACK;
+                let mut variance_sum = 0.0;
+                let mut weight_sum = 0.0;
+                for (ky, wy) in KERNEL.iter().enumerate() {
+                    for (kx, wx) in KERNEL.iter().enumerate() {
+                        // Taps outside the image are left out
+                        let qx = x as isize + (kx as isize - 2) * step as isize;
+                        let qy = y as isize + (ky as isize - 2) * step as isize;
+                        if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                            continue;
+                        }
+                        let q = qy as usize * width + qx as usize;
+                        // The pixel itself is kept whatever its guides are, eg.
+                        // with a zero normal averaged from opposite sides
+                        let weight = if q == p {
+                            wx * wy
+                        } else {
+                            let luminance_weight =
+                                (-(colors[p].luminance() - colors[q].luminance()).abs()
+                                    / deviation)
+                                    .exp();
+                            wx * wy
+                                * luminance_weight
+                                * self.edge_weight(&surfaces[p], &surfaces[q])
+                        };
+                        sum += &(colors[q] * weight);
+                        // Averaging reduces the variance by the square of
+                        // the weights
+                        variance_sum += weight * weight * variances[q];
+                        weight_sum += weight;
+                    }
+                }
+                // The center tap always has weight so this does not divide by zero
+                filtered.push(sum * (1.0 / weight_sum));
+                filtered_variances.push(variance_sum / (weight_sum * weight_sum));
+            }
+        }
+        (filtered, filtered_variances)
+    }
+
+    /// Return how much the pixel with surface `sq` should blur into the pixel
+    /// with surface `sp` based on the guiding passes.
+    fn edge_weight(&self, sp: &Surface, sq: &Surface) -> f32 {
+        let normal_weight = match (sp.object_id, sq.object_id) {
+            // Nothing hit in either means there is no edge between them
+            (0, 0) => 1.0,
+            (0, _) | (_, 0) => return 0.0,
+            _ => sp
+                .normal
+                .dot(&sq.normal)
+                .max(0.0)
+                .powf(self.normal_exponent),
+        };
+        let albedo_weight = (-distance_squared(sp.albedo, sq.albedo)
+            / (self.albedo_sigma * self.albedo_sigma))
+            .exp();
+        normal_weight * albedo_weight
+    }
+}
+
+/// Return the variances of the pixels in `frame`. Pixels with too few samples
+/// to know their own variance get the variance of the brightness among their
+/// neighbours instead.
+fn estimate_variances(frame: &Frame) -> Vec<f32> {
+    let (width, height) = (frame.width, frame.height);
+    (0..width * height)
+        .map(|p| {
+            if frame.sample_counts[p] >= 2 {
+                return frame.variances[p];
+            }
+            let (x, y) = (p % width, p / width);
+            let neighbours = (y.saturating_sub(1)..(y + 2).min(height))
+                .flat_map(|qy| {
+                    (x.saturating_sub(1)..(x + 2).min(width)).map(move |qx| qy * width + qx)
+                })
+                .map(|q| frame.colors[q].luminance())
+                .collect::<Vec<_>>();
+            let n = neighbours.len() as f32;
+            let mean = neighbours.iter().sum::<f32>() / n;
+            neighbours
+                .iter()
+                .map(|l| (l - mean) * (l - mean))
+                .sum::<f32>()
+                / n
+        })
+        .collect()
+}
+
+fn distance_squared(a: Color, b: Color) -> f32 {
+    let (r, g, b) = (a.r() - b.r(), a.g() - b.g(), a.b() - b.b());
+    r * r + g * g + b * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{film::ToneMapping, vector::Vector3};
+
+    /// Frame of noisy grey with a red half on the right.
+    fn noisy_frame() -> Frame {
+        let (width, height) = (32, 16);
+        let mut colors = Vec::new();
+        let mut variances = Vec::new();
+        let mut surfaces = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let noise = ((x * 37 + y * 91) % 11) as f32 / 25.0 - 0.2;
+                let albedo = if x < width / 2 {
+                    Color::new(0.5, 0.5, 0.5)
+                } else {
+                    Color::new(0.5, 0.0, 0.0)
+                };
+                colors.push(albedo * (1.0 + noise));
+                variances.push((albedo.luminance() * noise).powi(2));
+                surfaces.push(Surface {
+                    depth: 1.0,
+                    normal: Vector3 {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 1.0,
+                    },
+                    albedo,
+                    position: Vector3 {
+                        x: x as f32,
+                        y: y as f32,
+                        z: 0.0,
+                    },
+                    object_id: 1,
+                });
+            }
+        }
+        Frame {
+            width,
+            height,
+            colors,
+            sample_counts: vec![2; width * height],
+            variances,
+            tone_mapping: ToneMapping::Clamp,
+            surfaces: Some(surfaces),
+        }
+    }
+
+    #[test]
+    fn test_denoise_smooths_and_keeps_edges() {
+        let frame = noisy_frame();
+        let denoised = Denoiser::default().denoise(&frame);
+        for (i, c) in denoised.iter().enumerate() {
+            let albedo = frame.surfaces.as_ref().unwrap()[i].albedo;
+            // The noise is gone and no grey leaked into the red
+            assert!(
+                distance_squared(*c, albedo) < 0.01 * 0.01,
+                "{:?} at {}",
+                c,
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_denoise_zero_normal() {
+        let mut frame = noisy_frame();
+        // Normals of samples on opposite sides of a thin object cancel out
+        for surface in frame.surfaces.as_mut().unwrap().iter_mut() {
+            surface.normal = Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            };
+        }
+        let denoised = Denoiser::default().denoise(&frame);
+        assert!(denoised
+            .iter()
+            .all(|c| c.r().is_finite() && c.g().is_finite() && c.b().is_finite()));
+        // Nothing blurs across the missing normals
+        for (c, original) in denoised.iter().zip(frame.colors.iter()) {
+            assert!(distance_squared(*c, *original) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_denoise_keeps_converged_pixels() {
+        let mut frame = noisy_frame();
+        // No variance means the pattern is real detail
+        frame.variances = vec![0.0; frame.width * frame.height];
+        let denoised = Denoiser::default().denoise(&frame);
+        for (c, original) in denoised.iter().zip(frame.colors.iter()) {
+            assert!(distance_squared(*c, *original) < 1e-6);
+        }
+    }
+}
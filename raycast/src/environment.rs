@@ -0,0 +1,303 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path;
+
+use rand::{Rng, RngCore};
+
+use crate::{
+    color::{self, Color},
+    vector::{UnitVector3, Vector3},
+};
+
+/// The `environment` section of a scene file.
+#[derive(serde::Deserialize)]
+pub struct EnvironmentDescription {
+    /// Radiance HDR (`.hdr`) or OpenEXR (`.exr`) image in equirectangular
+    /// projection
+    pub path: path::PathBuf,
+    /// Degrees to turn the map around the vertical axis
+    #[serde(default)]
+    pub rotation: f32,
+    /// Multiplier for the colors in the map
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    /// Amount of shadow rays cast towards the map from each surface point
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_samples() -> usize {
+    16
+}
+
+/// An equirectangular image of the surroundings that is seen where rays escape
+/// the scene and that lights the scene from every direction.
+pub struct Environment {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    rotation: f32,
+    intensity: f32,
+    pub samples: usize,
+    distribution: Distribution,
+}
+
+impl Environment {
+    pub fn load(description: EnvironmentDescription) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = &description.path;
+        let (width, height, pixels) = match path.extension().and_then(|x| x.to_str()) {
+            Some("hdr") => read_hdr(path)?,
+            Some("exr") => read_exr(path)?,
+            _ => {
+                return Err(format!(
+                    "Environment map '{}' is not a Radiance HDR or OpenEXR file",
+                    path.display()
+                )
+                .into())
+            }
+        };
+
+        if width == 0 || height == 0 {
+            return Err(format!("Environment map '{}' is empty", path.display()).into());
+        }
+
+        let distribution = Distribution::new(width, height, &pixels);
+
+        Ok(Environment {
+            width,
+            height,
+            pixels,
+            rotation: utils::degs_to_rads(description.rotation),
+            intensity: description.intensity,
+            samples: description.samples,
+            distribution,
+        })
+    }
+
+    /// Return the color seen in `direction`.
+    pub fn color(&self, direction: &UnitVector3) -> Color {
+        // Turn the direction into the map's own orientation
+        let Vector3 { x, y, z } = rotate_y(Vector3::from(*direction), -self.rotation);
+        let u = 0.5 + x.atan2(-z) / (2.0 * PI);
+        let v = y.clamp(-1.0, 1.0).acos() / PI;
+        self.pixel(u, v) * self.intensity
+    }
+
+    /// Pick a direction towards the map so that bright areas are picked more
+    /// often. Return the direction, the color seen in it and the probability
+    /// density (over solid angle) of choosing it.
+    pub fn sample(&self, rng: &mut dyn RngCore) -> Option<(UnitVector3, Color, f32)> {
+        let (u, v, pdf) = self
+            .distribution
+            .sample(rng.gen::<f32>(), rng.gen::<f32>(), rng)?;
+
+        let (theta, phi) = (v * PI, (u - 0.5) * 2.0 * PI);
+        let sin_theta = theta.sin();
+        if sin_theta <= 0.0 {
+            return None;
+        }
+        let direction = rotate_y(
+            Vector3 {
+                x: sin_theta * phi.sin(),
+                y: theta.cos(),
+                z: -sin_theta * phi.cos(),
+            },
+            self.rotation,
+        )
+        .normalized();
+
+        // Convert the density over image area into density over solid angle
+        let pdf = pdf / (2.0 * PI * PI * sin_theta);
+
+        Some((direction, self.pixel(u, v) * self.intensity, pdf))
+    }
+
+    fn pixel(&self, u: f32, v: f32) -> Color {
+        let ix = ((u * self.width as f32) as usize).min(self.width - 1);
+        let iy = ((v * self.height as f32) as usize).min(self.height - 1);
+        self.pixels[iy * self.width + ix]
+    }
+}
+
+/// Piecewise-constant distribution over the pixels of the map for importance
+/// sampling. See "Physically Based Rendering" chapter 13.6.7.
+struct Distribution {
+    width: usize,
+    height: usize,
+    /// Cumulative sums of the rows, normalized into [0, 1]
+    marginal: Vec<f32>,
+    /// Cumulative sums inside each row, normalized into [0, 1]
+    conditional: Vec<f32>,
+    /// The weight of each pixel divided by the average weight
+    density: Vec<f32>,
+}
+
+impl Distribution {
+    fn new(width: usize, height: usize, pixels: &[Color]) -> Self {
+        let mut density = Vec::with_capacity(width * height);
+        for iy in 0..height {
+            // The rows near the poles are squeezed onto a smaller area of the
+            // sphere
+            let sin_theta = ((iy as f32 + 0.5) / height as f32 * PI).sin();
+            density.extend(
+                pixels[iy * width..(iy + 1) * width]
+                    .iter()
+                    .map(|c| c.luminance().max(0.0) * sin_theta),
+            );
+        }
+
+        let mut conditional = Vec::with_capacity(width * height);
+        let mut marginal = Vec::with_capacity(height);
+        let mut total = 0.0;
+        for row in density.chunks(width) {
+            let row_sum: f32 = row.iter().sum();
+            let mut acc = 0.0;
+            conditional.extend(row.iter().map(|w| {
+                acc += w;
+                if row_sum > 0.0 {
+                    acc / row_sum
+                } else {
+                    0.0
+                }
+            }));
+            total += row_sum;
+            marginal.push(total);
+        }
+        if total > 0.0 {
+            marginal.iter_mut().for_each(|x| *x /= total);
+        }
+
+        let average = total / (width * height) as f32;
+        density
+            .iter_mut()
+            .for_each(|w| *w = if average > 0.0 { *w / average } else { 0.0 });
+
+        Distribution {
+            width,
+            height,
+            marginal,
+            conditional,
+            density,
+        }
+    }
+
+    /// Map two uniform random numbers into image coordinates `u` and `v` in
+    /// [0, 1] and return them with their probability density. `rng` spreads
+    /// the point inside the chosen pixel.
+    fn sample(&self, r1: f32, r2: f32, rng: &mut dyn RngCore) -> Option<(f32, f32, f32)> {
+        let iy = search(&self.marginal, r1)?;
+        let row = &self.conditional[iy * self.width..(iy + 1) * self.width];
+        let ix = search(row, r2)?;
+
+        let pdf = self.density[iy * self.width + ix];
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        // Spread the sample inside the chosen pixel
+        let u = (ix as f32 + rng.gen::<f32>()) / self.width as f32;
+        let v = (iy as f32 + rng.gen::<f32>()) / self.height as f32;
+        Some((u, v, pdf))
+    }
+}
+
+/// Find the first index in the cumulative distribution `cdf` that is past `x`.
+fn search(cdf: &[f32], x: f32) -> Option<usize> {
+    match cdf.last() {
+        Some(&last) if last > 0.0 => (),
+        _ => return None,
+    }
+    let i = cdf.partition_point(|&c| c <= x);
+    Some(i.min(cdf.len() - 1))
+}
+
+/// Rotate `v` by `rads` around the y-axis.
+fn rotate_y(v: Vector3, rads: f32) -> Vector3 {
+    let (sin, cos) = rads.sin_cos();
+    Vector3 {
+        x: v.x * cos + v.z * sin,
+        y: v.y,
+        z: -v.x * sin + v.z * cos,
+    }
+}
+
+type Pixels = (usize, usize, Vec<Color>);
+
+fn read_hdr(path: &path::Path) -> Result<Pixels, Box<dyn std::error::Error>> {
+    let file = io::BufReader::new(fs::File::open(path)?);
+    let decoder = image::codecs::hdr::HdrDecoder::new(file)?;
+    let meta = decoder.metadata();
+    let pixels = decoder
+        .read_image_hdr()?
+        .into_iter()
+        .map(|image::Rgb([r, g, b])| Color::new(r, g, b))
+        .collect();
+    Ok((meta.width as usize, meta.height as usize, pixels))
+}
+
+fn read_exr(path: &path::Path) -> Result<Pixels, Box<dyn std::error::Error>> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| {
+            (
+                resolution.width(),
+                resolution.height(),
+                vec![color::consts::BLACK; resolution.width() * resolution.height()],
+            )
+        },
+        |(width, _, pixels): &mut Pixels, position, (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels[position.y() * *width + position.x()] = Color::new(r, g, b);
+        },
+    )?;
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution_prefers_bright_pixels() {
+        // Single bright pixel in the middle row
+        let (width, height) = (4, 3);
+        let mut pixels = vec![color::consts::BLACK; width * height];
+        pixels[width + 2] = color::consts::WHITE;
+        let distribution = Distribution::new(width, height, &pixels);
+
+        for &(r1, r2) in &[(0.0, 0.0), (0.5, 0.5), (0.99, 0.99)] {
+            let (u, v, pdf) = distribution
+                .sample(r1, r2, &mut rand::thread_rng())
+                .unwrap();
+            assert_eq!((u * width as f32) as usize, 2);
+            assert_eq!((v * height as f32) as usize, 1);
+            assert!((pdf - (width * height) as f32).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_distribution_of_black_map() {
+        let pixels = vec![color::consts::BLACK; 4];
+        let distribution = Distribution::new(2, 2, &pixels);
+        assert!(distribution
+            .sample(0.5, 0.5, &mut rand::thread_rng())
+            .is_none());
+    }
+
+    #[test]
+    fn test_rotate_y_quarter_turn() {
+        let v = rotate_y(
+            Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            PI / 2.0,
+        );
+        assert!((v.x - -1.0).abs() < 1e-6 && v.y.abs() < 1e-6 && v.z.abs() < 1e-6);
+    }
+}
@@ -0,0 +1,253 @@
+use std::str;
+
+use crate::{
+    camera::CameraDescription,
+    color::{self, Color},
+    sky,
+};
+
+/// Exposure settings in the `camera` section of a scene file.
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+pub enum Exposure {
+    /// Exposure value at ISO 100; each step up halves the brightness.
+    Ev100(f32),
+    /// Exposure from camera settings: sensitivity, shutter time in seconds
+    /// and the f-number of the aperture.
+    Manual {
+        iso: f32,
+        shutter: f32,
+        f_number: f32,
+    },
+}
+
+impl Exposure {
+    pub fn ev100(&self) -> f32 {
+        match *self {
+            Exposure::Ev100(ev) => ev,
+            Exposure::Manual {
+                iso,
+                shutter,
+                f_number,
+            } => (f_number * f_number / shutter * 100.0 / iso).log2(),
+        }
+    }
+
+    /// Return the factor that scales radiance into the brightness on the
+    /// sensor. Based on the saturation-based sensitivity as in "Moving
+    /// Frostbite to Physically Based Rendering" by Lagarde and de Rousiers.
+    pub fn multiplier(&self) -> f32 {
+        1.0 / (1.2 * 2f32.powf(self.ev100()))
+    }
+}
+
+/// Curve that maps linear radiance of any brightness into the range [0, 1]
+/// of an 8-bit image.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMapping {
+    /// Values above one are cut off
+    #[default]
+    Clamp,
+    /// Each channel `x` turns into `x / (1 + x)`
+    Reinhard,
+    /// Filmic curve fitted to the ACES reference rendering transform by
+    /// Krzysztof Narkowicz
+    Aces,
+    /// Troy Sobotka's AgX that also desaturates the brightest colors towards
+    /// white, as approximated by Benjamin Wrensch
+    AgX,
+}
+
+impl ToneMapping {
+    /// Map the linear `color` into linear values in [0, 1].
+    pub fn apply(&self, color: Color) -> Color {
+        let (r, g, b) = (color.r().max(0.0), color.g().max(0.0), color.b().max(0.0));
+        match self {
+            ToneMapping::Clamp => Color::new(r.min(1.0), g.min(1.0), b.min(1.0)),
+            ToneMapping::Reinhard => Color::new(r / (1.0 + r), g / (1.0 + g), b / (1.0 + b)),
+            ToneMapping::Aces => Color::new(aces(r), aces(g), aces(b)),
+            ToneMapping::AgX => agx(r, g, b),
+        }
+    }
+}
+
+impl str::FromStr for ToneMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clamp" => Ok(ToneMapping::Clamp),
+            "reinhard" => Ok(ToneMapping::Reinhard),
+            "aces" => Ok(ToneMapping::Aces),
+            "agx" => Ok(ToneMapping::AgX),
+            _ => Err(format!(
+                "Unknown tone mapping '{}'; expected one of Clamp, Reinhard, Aces or AgX",
+                s
+            )),
+        }
+    }
+}
+
+fn aces(x: f32) -> f32 {
+    (x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
+
+/// Exposure values below and above middle grey that AgX fits into the image
+const AGX_MIN_EV: f32 = -12.473_93;
+const AGX_MAX_EV: f32 = 4.026_069;
+
+fn agx(r: f32, g: f32, b: f32) -> Color {
+    // Squeeze the colors inwards from the primaries so that bright saturated
+    // colors blend into white
+    let inset = [
+        0.842_479 * r + 0.078_434 * g + 0.079_224 * b,
+        0.042_328 * r + 0.878_469 * g + 0.079_166 * b,
+        0.042_376 * r + 0.078_434 * g + 0.879_143 * b,
+    ];
+    let [r, g, b] = inset.map(|x| {
+        // Encode logarithmically and apply the contrast curve
+        let x = (x.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV) - AGX_MIN_EV)
+            / (AGX_MAX_EV - AGX_MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    // Undo the inset and the display encoding of the curve
+    Color::new(
+        (1.196_879 * r - 0.098_021 * g - 0.099_03 * b)
+            .max(0.0)
+            .powf(2.2)
+            .min(1.0),
+        (-0.052_897 * r + 1.151_903 * g - 0.098_961 * b)
+            .max(0.0)
+            .powf(2.2)
+            .min(1.0),
+        (-0.052_972 * r - 0.098_043 * g + 1.151_074 * b)
+            .max(0.0)
+            .powf(2.2)
+            .min(1.0),
+    )
+}
+
+/// Color temperature that is white without white balancing, ie. that of the
+/// sRGB white point D65
+const NEUTRAL_TEMPERATURE: f32 = 6504.0;
+
+/// Adjustments made to the linear radiance of a pixel before it is written
+/// out.
+#[derive(Clone, Copy, Debug)]
+pub struct Film {
+    exposure: f32,
+    white_balance: Color,
+    /// Applied only when encoding into 8 bits as other formats keep the
+    /// radiance linear
+    pub tone_mapping: ToneMapping,
+}
+
+impl From<&CameraDescription> for Film {
+    fn from(description: &CameraDescription) -> Self {
+        Film {
+            exposure: description.exposure.map_or(1.0, |x| x.multiplier()),
+            white_balance: description
+                .white_balance
+                .map_or(color::consts::WHITE, white_balance),
+            tone_mapping: description.tone_mapping,
+        }
+    }
+}
+
+impl Film {
+    /// Apply the exposure and white balance to the linear `color`.
+    pub fn develop(&self, color: Color) -> Color {
+        color * self.white_balance * self.exposure
+    }
+}
+
+/// Return the channel multipliers that make light of `temperature` Kelvin
+/// look white (von Kries adaptation in linear sRGB).
+fn white_balance(temperature: f32) -> Color {
+    let [r, g, b] = blackbody_rgb(temperature);
+    let [nr, ng, nb] = blackbody_rgb(NEUTRAL_TEMPERATURE);
+    Color::new(nr / r, ng / g, nb / b)
+}
+
+/// Return the linear sRGB color of unit luminance of a black body at
+/// `temperature` Kelvin. Uses the approximation of the Planckian locus from
+/// "Design of advanced color temperature control system for HDTV
+/// applications" by Kim et al.
+fn blackbody_rgb(temperature: f32) -> [f32; 3] {
+    let t = temperature.clamp(1667.0, 25000.0);
+    let (t2, t3) = (t * t, t * t * t);
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.107038e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.3481102 * x2 + 2.185558 * x - 0.2021968
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.374186 * x2 + 2.09137 * x - 0.1674887
+    } else {
+        3.081758 * x3 - 5.873387 * x2 + 3.75113 * x - 0.3700148
+    };
+    let c = sky::xyy_to_rgb(x, y, 1.0);
+    // Keep the channels positive for dividing by them
+    [c.r().max(1e-4), c.g().max(1e-4), c.b().max(1e-4)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_exposure_matches_ev() {
+        // Sunny 16 rule: f/16 at 1/100 s on ISO 100 is about EV 15
+        let manual = Exposure::Manual {
+            iso: 100.0,
+            shutter: 1.0 / 100.0,
+            f_number: 16.0,
+        };
+        assert!((manual.ev100() - 14.64).abs() < 0.01);
+        let ev = Exposure::Ev100(1.0);
+        assert!((ev.multiplier() - 1.0 / 2.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tone_mapping() {
+        for &mapping in &[
+            ToneMapping::Clamp,
+            ToneMapping::Reinhard,
+            ToneMapping::Aces,
+            ToneMapping::AgX,
+        ] {
+            // Brighter input never maps darker and stays inside [0, 1]
+            let mut previous = 0.0;
+            for i in 0..100 {
+                let x = 0.01 * 1.2f32.powi(i);
+                let y = mapping.apply(Color::new(x, x, x)).g();
+                assert!(y >= previous - 1e-6 && y <= 1.0, "{:?} at {}", mapping, x);
+                previous = y;
+            }
+        }
+        // Unlike clamping the others still tell highlights apart
+        let highlight = |mapping: ToneMapping, x| mapping.apply(Color::new(x, x, x)).g();
+        assert_eq!(
+            highlight(ToneMapping::Clamp, 2.0),
+            highlight(ToneMapping::Clamp, 4.0)
+        );
+        assert!(highlight(ToneMapping::Reinhard, 2.0) < highlight(ToneMapping::Reinhard, 4.0));
+        assert!(highlight(ToneMapping::AgX, 2.0) < highlight(ToneMapping::AgX, 4.0));
+    }
+
+    #[test]
+    fn test_white_balance() {
+        // Neutral temperature changes nothing
+        let neutral = white_balance(NEUTRAL_TEMPERATURE);
+        let (r, g, b) = (neutral.r(), neutral.g(), neutral.b());
+        assert!((r - 1.0).abs() < 1e-6 && (g - 1.0).abs() < 1e-6 && (b - 1.0).abs() < 1e-6);
+
+        // Warm light is made white by reducing red and boosting blue
+        let warm = white_balance(3200.0);
+        assert!(warm.r() < 1.0 && warm.b() > 1.0);
+    }
+}
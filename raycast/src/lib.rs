@@ -1,19 +1,26 @@
+pub mod aov;
+pub mod bounds;
 pub mod camera;
+pub mod denoise;
+mod environment;
+pub mod film;
+pub mod light;
 mod matrix;
 mod objects;
-mod ray;
+pub mod ray;
 pub mod raycaster;
+pub mod sampler;
 pub mod scene;
-mod vector;
+mod sky;
+pub mod stereo;
+mod transform;
+pub mod vector;
 
-use std::convert::TryFrom;
-
-use rand::random;
+use rand::{Rng, RngCore};
 
 use crate::{
-    matrix::SquareMatrix4,
     ray::Ray,
-    vector::{UnitVector3, Vector3, Vector4},
+    vector::{UnitVector3, Vector3},
 };
 
 #[derive(Debug)]
@@ -29,73 +36,6 @@ pub trait Intersect {
     fn intersect(&self, ray: &Ray, tmin: f32) -> Option<Intersection>;
 }
 
-// TODO It isn't exactly good to parse a matrix from some invented language
-impl TryFrom<&str> for SquareMatrix4 {
-    type Error = String; // TODO ParseError
-
-    fn try_from(parse_string: &str) -> Result<Self, Self::Error> {
-        let mat = parse_string
-            .trim()
-            .split_terminator(';')
-            .map(|s| {
-                let s = s.trim();
-                if s.starts_with("Translate") {
-                    let v: Vec<f32> = s[s.find('e').unwrap() + 1..]
-                        .split_ascii_whitespace()
-                        .map(|q| q.trim().parse::<f32>().unwrap()) //TODO
-                        .collect();
-                    SquareMatrix4::translation(Vector4 {
-                        x: v[0],
-                        y: v[1],
-                        z: v[2],
-                        w: 1.0,
-                    })
-                } else if s.starts_with("RotX") {
-                    let rads = utils::degs_to_rads(
-                        s[s.find('X').unwrap() + 1..].trim().parse::<f32>().unwrap(),
-                    ); //TODO
-                    SquareMatrix4::rot_x(rads)
-                } else if s.starts_with("RotY") {
-                    let rads = utils::degs_to_rads(
-                        s[s.find('Y').unwrap() + 1..].trim().parse::<f32>().unwrap(),
-                    ); //TODO
-                    SquareMatrix4::rot_y(rads)
-                } else if s.starts_with("RotZ") {
-                    let rads = utils::degs_to_rads(
-                        s[s.find('Z').unwrap() + 1..].trim().parse::<f32>().unwrap(),
-                    ); //TODO
-                    SquareMatrix4::rot_z(rads)
-                } else if s.starts_with("Scale") {
-                    let v: Vec<f32> = s[s.find('e').unwrap() + 1..]
-                        .split_ascii_whitespace()
-                        .map(|q| q.trim().parse::<f32>().unwrap()) // TODO
-                        .collect();
-                    if let [a] = v[0..] {
-                        // Scale all 3 dimensions the same
-                        SquareMatrix4::scale(Vector4 {
-                            x: a,
-                            y: a,
-                            z: a,
-                            w: a,
-                        })
-                    } else if let [x, y, z] = v[0..] {
-                        // Scale each differently
-                        SquareMatrix4::scale(Vector4 { x, y, z, w: 1.0 })
-                    } else {
-                        // TODO return Err
-                        panic!("Insufficient number of scaling values")
-                    }
-                } else {
-                    // TODO return Err
-                    panic!("Transformation not found '{}'", s)
-                }
-            })
-            .fold(SquareMatrix4::identity(), |acc, x| &acc * &x);
-
-        Ok(mat)
-    }
-}
-
 #[derive(serde::Deserialize, Clone, Copy, Debug)]
 pub struct Material {
     pub color: color::Color,
@@ -113,14 +53,6 @@ impl std::default::Default for Material {
     }
 }
 
-#[derive(serde::Deserialize)]
-pub struct Light {
-    pub position: Vector3,
-    pub _direction: Option<Vector3>,
-    pub color: color::Color,
-    pub intensity: f32,
-}
-
 pub mod color {
     use super::vector::Vector3;
     use image::Rgb;
@@ -144,26 +76,53 @@ pub mod color {
         pub const fn new(r: f32, g: f32, b: f32) -> Self {
             Color(Vector3 { x: r, y: g, z: b })
         }
+
+        /// Relative luminance of the linear color (Rec. 709 weights).
+        pub fn luminance(&self) -> f32 {
+            0.2126 * self.0.x + 0.7152 * self.0.y + 0.0722 * self.0.z
+        }
+
+        pub fn r(&self) -> f32 {
+            self.0.x
+        }
+
+        pub fn g(&self) -> f32 {
+            self.0.y
+        }
+
+        pub fn b(&self) -> f32 {
+            self.0.z
+        }
     }
 
     impl From<Color> for Rgb<u8> {
         fn from(c: Color) -> Self {
-            // Taking the square root applies "gamma 2"
             Rgb(c.into())
         }
     }
 
     impl From<Color> for [u8; 3] {
+        /// Encode the linear color with the sRGB transfer function cutting off
+        /// values outside [0, 1].
         fn from(c: Color) -> Self {
-            // Taking the square root applies "gamma 2"
             [
-                (c.0.x.sqrt().clamp(0.0, 1.0) * (u8::MAX as f32 + 1.0)) as u8,
-                (c.0.y.sqrt().clamp(0.0, 1.0) * (u8::MAX as f32 + 1.0)) as u8,
-                (c.0.z.sqrt().clamp(0.0, 1.0) * (u8::MAX as f32 + 1.0)) as u8,
+                (srgb_encode(c.0.x) * (u8::MAX as f32 + 1.0)) as u8,
+                (srgb_encode(c.0.y) * (u8::MAX as f32 + 1.0)) as u8,
+                (srgb_encode(c.0.z) * (u8::MAX as f32 + 1.0)) as u8,
             ]
         }
     }
 
+    /// The piecewise sRGB curve from linear values into display values.
+    fn srgb_encode(x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        if x <= 0.003_130_8 {
+            12.92 * x
+        } else {
+            1.055 * x.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
     impl<'de> serde::Deserialize<'de> for Color {
         fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where
@@ -193,6 +152,18 @@ pub mod color {
         }
     }
 
+    impl std::ops::Mul for Color {
+        type Output = Self;
+        /// Multiply component-wise, eg. to filter light by a surface color.
+        fn mul(self, other: Color) -> Self::Output {
+            Color::new(
+                self.0.x * other.0.x,
+                self.0.y * other.0.y,
+                self.0.z * other.0.z,
+            )
+        }
+    }
+
     impl std::ops::Add for Color {
         type Output = Self;
         fn add(self, other: Color) -> Self::Output {
@@ -222,22 +193,22 @@ pub enum Surface {
 }
 
 impl Surface {
-    pub fn surface(&self, intersection: &Intersection) -> UnitVector3 {
+    pub fn surface(&self, intersection: &Intersection, rng: &mut dyn RngCore) -> UnitVector3 {
         match self {
             Surface::Normal => intersection.normal,
-            Surface::Diffuse => diffuse(intersection),
+            Surface::Diffuse => diffuse(intersection, rng),
             Surface::PerfectReflection => intersection.incoming.reflect(&intersection.normal),
         }
     }
 }
 
-fn diffuse(intersection: &Intersection) -> UnitVector3 {
+fn diffuse(intersection: &Intersection, rng: &mut dyn RngCore) -> UnitVector3 {
     // Based on
     // https://raytracing.github.io/books/RayTracingInOneWeekend.html#diffusematerials/truelambertianreflection
     let random_sphere_point = Vector3 {
-        x: random::<f32>() - 0.5,
-        y: random::<f32>() - 0.5,
-        z: random::<f32>() - 0.5,
+        x: rng.gen::<f32>() - 0.5,
+        y: rng.gen::<f32>() - 0.5,
+        z: rng.gen::<f32>() - 0.5,
     }
     .normalized();
 
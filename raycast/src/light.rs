@@ -0,0 +1,325 @@
+use rand::{Rng, RngCore};
+
+use crate::{
+    color::Color,
+    vector::{UnitVector3, Vector3},
+};
+
+#[derive(serde::Deserialize)]
+pub struct Light {
+    pub position: Vector3,
+    pub color: Color,
+    pub intensity: f32,
+    #[serde(default)]
+    pub kind: LightKind,
+    #[serde(default)]
+    pub falloff: Falloff,
+    /// Amount of shadow rays cast towards an area light. This is rounded up to
+    /// a square number in order to stratify the samples into a grid.
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+}
+
+fn default_samples() -> usize {
+    16
+}
+
+/// Select how light spreads out from its position.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub enum LightKind {
+    /// Shines equally to every direction.
+    #[default]
+    Point,
+    /// Shines in a cone around `direction`. The angles are given in degrees
+    /// between `direction` and the edge of the cone; inside `inner_angle` the
+    /// light is at full intensity and it fades out smoothly until
+    /// `outer_angle`.
+    Spot {
+        direction: UnitVector3,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    /// Parallelogram spanned by the two edges and centered at the light's
+    /// position.
+    Rectangle { edges: [Vector3; 2] },
+    /// Flat disc centered at the light's position.
+    Disc { normal: UnitVector3, radius: f32 },
+    /// Ball centered at the light's position.
+    Sphere { radius: f32 },
+}
+
+/// Select how the intensity of a light decreases over distance.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub enum Falloff {
+    /// Same intensity at any distance.
+    None,
+    /// Intensity is divided by the distance.
+    #[default]
+    Linear,
+    /// Intensity is divided by the squared distance.
+    InverseSquare,
+    /// Intensity is divided by `constant + linear * d + quadratic * d^2` like
+    /// in many other renderers.
+    Coefficients {
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    },
+    /// The light's intensity is its radiant power in watts, which spreads out
+    /// evenly over a sphere around the light.
+    Physical,
+}
+
+impl Falloff {
+    /// Return the multiplier for intensity at `distance` from the light.
+    pub fn attenuation(&self, distance: f32) -> f32 {
+        match *self {
+            Falloff::None => 1.0,
+            Falloff::Linear => 1.0 / distance,
+            Falloff::InverseSquare => 1.0 / distance.powi(2),
+            Falloff::Coefficients {
+                constant,
+                linear,
+                quadratic,
+            } => {
+                // Avoid dividing by zero on odd coefficients
+                1.0 / f32::max(
+                    f32::EPSILON,
+                    constant + linear * distance + quadratic * distance.powi(2),
+                )
+            }
+            Falloff::Physical => 1.0 / (4.0 * std::f32::consts::PI * distance.powi(2)),
+        }
+    }
+}
+
+impl Light {
+    /// Return the fraction in [0, 1] of light that reaches a point which sees
+    /// the light in the direction of `towards_light`.
+    pub fn emission_factor(&self, towards_light: &UnitVector3) -> f32 {
+        match self.kind {
+            LightKind::Point | LightKind::Sphere { .. } => 1.0,
+            LightKind::Spot {
+                direction,
+                inner_angle,
+                outer_angle,
+            } => {
+                // Compare the cosines instead of angles to avoid an `acos`
+                let cos_angle = (-*towards_light).dot(&direction);
+                let cos_inner = utils::degs_to_rads(inner_angle).cos();
+                let cos_outer = utils::degs_to_rads(outer_angle).cos();
+                smoothstep(cos_outer, cos_inner, cos_angle)
+            }
+            // Flat lights shine from both sides, but less so when seen from
+            // the side
+            LightKind::Rectangle { edges: [u, v] } => {
+                towards_light.dot(&u.cross(&v).normalized()).abs()
+            }
+            LightKind::Disc { normal, .. } => towards_light.dot(&normal).abs(),
+        }
+    }
+
+    /// Return points on the light to cast shadow rays at from `point`. Area
+    /// lights are sampled in a jittered grid, so that the shadow rays cover the
+    /// light evenly and the penumbras come out smooth.
+    pub fn sample_points(&self, point: Vector3, rng: &mut dyn RngCore) -> Vec<Vector3> {
+        let center = self.position;
+        match self.kind {
+            LightKind::Point | LightKind::Spot { .. } => vec![center],
+            LightKind::Rectangle { edges: [u, v] } => stratified(self.samples, rng)
+                .map(|(s, t)| center + (s - 0.5) * u + (t - 0.5) * v)
+                .collect(),
+            LightKind::Disc { normal, radius } => {
+                let (u, v) = normal.orthonormal_basis();
+                stratified(self.samples, rng)
+                    .map(|(s, t)| {
+                        // Square root spreads the points evenly by area
+                        let r = radius * s.sqrt();
+                        let phi = 2.0 * std::f32::consts::PI * t;
+                        center + (r * phi.cos()) * u + (r * phi.sin()) * v
+                    })
+                    .collect()
+            }
+            LightKind::Sphere { radius } => {
+                // Only the hemisphere facing the point can be seen from it
+                let w = match point - center {
+                    v if v.length() > radius => v.normalized(),
+                    _ => return vec![center],
+                };
+                let (u, v) = w.orthonormal_basis();
+                stratified(self.samples, rng)
+                    .map(|(s, t)| {
+                        // Uniform height on a hemisphere is uniform by area
+                        let z = s;
+                        let r = (1.0 - z * z).max(0.0).sqrt();
+                        let phi = 2.0 * std::f32::consts::PI * t;
+                        center + radius * (r * phi.cos() * u + r * phi.sin() * v + z * w)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Generate at least `n` points in the unit square, one randomly inside each
+/// cell of a square grid.
+fn stratified<'a>(n: usize, rng: &'a mut dyn RngCore) -> impl Iterator<Item = (f32, f32)> + 'a {
+    let side = (n.max(1) as f32).sqrt().ceil() as usize;
+    let cell = 1.0 / side as f32;
+    (0..side * side).map(move |i| {
+        let (col, row) = (i % side, i / side);
+        (
+            (col as f32 + rng.gen::<f32>()) * cell,
+            (row as f32 + rng.gen::<f32>()) * cell,
+        )
+    })
+}
+
+/// Hermite interpolation from 0 to 1 when `x` goes from `edge0` to `edge1`.
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        // No room for falloff so cut off hard
+        return if x >= edge0 { 1.0 } else { 0.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::consts::WHITE;
+
+    fn spot(inner_angle: f32, outer_angle: f32) -> Light {
+        Light {
+            position: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            color: WHITE,
+            intensity: 1.0,
+            kind: LightKind::Spot {
+                direction: Vector3 {
+                    x: 0.0,
+                    y: -1.0,
+                    z: 0.0,
+                }
+                .normalized(),
+                inner_angle,
+                outer_angle,
+            },
+            falloff: Falloff::default(),
+            samples: 1,
+        }
+    }
+
+    fn towards_light_at(degs: f32) -> UnitVector3 {
+        // Direction from a point below the light, tilted by `degs` from the
+        // spot's axis
+        let rads = utils::degs_to_rads(degs);
+        Vector3 {
+            x: -rads.sin(),
+            y: rads.cos(),
+            z: 0.0,
+        }
+        .normalized()
+    }
+
+    #[test]
+    fn test_spot_inside_inner_cone() {
+        let light = spot(20.0, 30.0);
+        assert!((light.emission_factor(&towards_light_at(0.0)) - 1.0).abs() < 1e-6);
+        assert!((light.emission_factor(&towards_light_at(19.0)) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_spot_outside_outer_cone() {
+        let light = spot(20.0, 30.0);
+        assert_eq!(light.emission_factor(&towards_light_at(31.0)), 0.0);
+        assert_eq!(light.emission_factor(&towards_light_at(90.0)), 0.0);
+    }
+
+    #[test]
+    fn test_spot_falloff_decreases() {
+        let light = spot(20.0, 30.0);
+        let a = light.emission_factor(&towards_light_at(22.0));
+        let b = light.emission_factor(&towards_light_at(25.0));
+        let c = light.emission_factor(&towards_light_at(28.0));
+        assert!(1.0 > a && a > b && b > c && c > 0.0);
+    }
+
+    #[test]
+    fn test_spot_without_falloff() {
+        let light = spot(30.0, 30.0);
+        assert_eq!(light.emission_factor(&towards_light_at(29.0)), 1.0);
+        assert_eq!(light.emission_factor(&towards_light_at(31.0)), 0.0);
+    }
+
+    #[test]
+    fn test_stratified_covers_every_cell() {
+        let points: Vec<(f32, f32)> = stratified(10, &mut rand::thread_rng()).collect();
+        // Rounded up to a 4 by 4 grid
+        assert_eq!(points.len(), 16);
+        for (i, (s, t)) in points.into_iter().enumerate() {
+            assert_eq!((s * 4.0) as usize, i % 4);
+            assert_eq!((t * 4.0) as usize, i / 4);
+        }
+    }
+
+    #[test]
+    fn test_disc_samples_inside_disc() {
+        let light = Light {
+            position: Vector3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+            color: WHITE,
+            intensity: 1.0,
+            kind: LightKind::Disc {
+                normal: Vector3 {
+                    x: 0.0,
+                    y: -1.0,
+                    z: 0.0,
+                }
+                .normalized(),
+                radius: 0.5,
+            },
+            falloff: Falloff::default(),
+            samples: 64,
+        };
+        let point = Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        for p in light.sample_points(point, &mut rand::thread_rng()) {
+            let offset = p - light.position;
+            assert!(offset.y.abs() < 1e-6);
+            assert!(offset.length() <= 0.5 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_falloff_coefficients_match_named_models() {
+        let linear = Falloff::Coefficients {
+            constant: 0.0,
+            linear: 1.0,
+            quadratic: 0.0,
+        };
+        let inverse_square = Falloff::Coefficients {
+            constant: 0.0,
+            linear: 0.0,
+            quadratic: 1.0,
+        };
+        for &d in &[0.5, 1.0, 3.0, 10.0] {
+            assert!((linear.attenuation(d) - Falloff::Linear.attenuation(d)).abs() < 1e-6);
+            assert!(
+                (inverse_square.attenuation(d) - Falloff::InverseSquare.attenuation(d)).abs()
+                    < 1e-6
+            );
+            assert_eq!(Falloff::None.attenuation(d), 1.0);
+        }
+    }
+}
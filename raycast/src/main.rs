@@ -1,16 +1,34 @@
+use std::fs;
 use std::io::{self, Write};
 use std::path;
+use std::sync;
+use std::time;
 
-use image::RgbImage;
+use image::{ImageBuffer, Primitive, Rgb};
 use terminal_toys as tt;
 
-use raycast::{camera, raycaster, scene};
+use raycast::{aov, camera, denoise, film, raycaster, scene, stereo};
 
 struct Args(
     path::PathBuf,
     usize,
     usize,
     usize,
+    usize,
+    usize,
+    f32,
+    bool,
+    bool,
+    bool,
+    usize,
+    f32,
+    usize,
+    String,
+    bool,
+    String,
+    bool,
+    bool,
+    String,
     bool,
     tt::smargs::Result<path::PathBuf>,
 );
@@ -39,11 +57,99 @@ fn cli_args() -> Result<Args, String> {
                 ["t", "threads"],
                 tt::smargs::Kind::Optional("1")
             ),
+            (
+                "Amount of rays shot through each pixel (0 uses the scene's)",
+                ["n", "samples"],
+                tt::smargs::Kind::Optional("0")
+            ),
+            (
+                "Amount of times rays are traced after hitting something \
+                (0 uses the scene's)",
+                ["depth"],
+                tt::smargs::Kind::Optional("0")
+            ),
+            (
+                "Stop sampling pixels when their noise is below this fraction \
+                of their brightness (0 uses the scene's)",
+                ["noise-threshold"],
+                tt::smargs::Kind::Optional("0")
+            ),
+            (
+                "If should also save an image of the amount of samples taken \
+                in each pixel",
+                ["heatmap"],
+                tt::smargs::Kind::Flag
+            ),
+            (
+                "If should also save the depth, normal, albedo, position and \
+                object ID passes; as channels of the same file if it is \
+                OpenEXR and otherwise into separate files",
+                ["aovs"],
+                tt::smargs::Kind::Flag
+            ),
+            (
+                "If should smooth out the noise of the finished render \
+                keeping the edges in the normal and albedo passes",
+                ["denoise"],
+                tt::smargs::Kind::Flag
+            ),
+            (
+                "Render progressively in this many passes that each add \
+                samples to the whole image (0 or 1 renders in one go)",
+                ["p", "passes"],
+                tt::smargs::Kind::Optional("0")
+            ),
+            (
+                "Seconds between writing the image rendered so far when \
+                rendering progressively (0 for never)",
+                ["write-seconds"],
+                tt::smargs::Kind::Optional("10")
+            ),
+            (
+                "Passes between writing the image rendered so far when \
+                rendering progressively (0 for never)",
+                ["write-passes"],
+                tt::smargs::Kind::Optional("0")
+            ),
+            (
+                "Tone mapping used for 8-bit images: Clamp, Reinhard, Aces \
+                or AgX (empty uses the camera's)",
+                ["tone-mapping"],
+                tt::smargs::Kind::Optional("")
+            ),
             (
                 "If should use debug coloring",
                 ["d", "debug"],
                 tt::smargs::Kind::Flag
             ),
+            (
+                "Name of the camera in the scene's 'cameras' to render from",
+                ["c", "camera"],
+                tt::smargs::Kind::Optional("")
+            ),
+            (
+                "If should render from every camera in the scene's 'cameras' \
+                adding the camera name to the output filename",
+                ["all-cameras"],
+                tt::smargs::Kind::Flag
+            ),
+            (
+                "If should move the camera so that every object fits in view",
+                ["f", "frame"],
+                tt::smargs::Kind::Flag
+            ),
+            (
+                "Render only the part of the image in the rectangle \
+                'x,y,width,height' counted in pixels from the top left",
+                ["region"],
+                tt::smargs::Kind::Optional("")
+            ),
+            (
+                "If should save a region in an image of the full size with \
+                the rest left black instead of cropping to the region",
+                ["full-size"],
+                tt::smargs::Kind::Flag
+            ),
             (
                 "Output path of the render",
                 ["o", "out"],
@@ -122,11 +228,32 @@ impl Args {
 }
 
 pub fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let Args(source_path, width, height, thread_count, is_debug, output_path) = cli_args()
-        .unwrap_or_else(|e| {
-            eprintln!("{}", e);
-            std::process::exit(1);
-        });
+    let Args(
+        source_path,
+        width,
+        height,
+        thread_count,
+        samples,
+        depth,
+        noise_threshold,
+        is_heatmap,
+        is_aovs,
+        is_denoise,
+        passes,
+        write_seconds,
+        write_passes,
+        tone_mapping,
+        is_debug,
+        camera_name,
+        is_all_cameras,
+        is_framed,
+        region,
+        is_full_size,
+        output_path,
+    ) = cli_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
     // Unwrap and check output path.
     // TODO Relative paths
@@ -134,24 +261,359 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
     // (filename still with the same old format!)
     let output_path = Args::handle_output_path(width, height, &source_path, output_path)?;
 
-    let raycaster = {
-        let scene = scene::Scene::from_file(&source_path)?;
-        let camera = camera::PerspectiveCamera::with_view(scene.fov, width, height);
-        raycaster::Raycaster { scene, camera }
+    let scene = sync::Arc::new(scene::Scene::from_file(&source_path)?);
+
+    // Command line overrides the settings in the scene
+    let mut settings = scene.render;
+    if samples > 0 {
+        settings.samples = samples;
+    }
+    if depth > 0 {
+        settings.depth = depth;
+    }
+    if noise_threshold > 0.0 {
+        settings.noise_threshold = Some(noise_threshold);
+    }
+    let is_aovs = is_aovs || settings.aovs;
+    // The denoiser is guided by the passes
+    settings.aovs = is_aovs || is_denoise;
+    // Passes beyond the amount of samples are left out of the render
+    let passes = raycaster::pass_samples(settings.samples, passes).len();
+
+    let tone_mapping = if tone_mapping.is_empty() {
+        None
+    } else {
+        Some(tone_mapping.parse::<film::ToneMapping>()?)
     };
 
-    let image = RgbImage::from_vec(
-        width as u32,
-        height as u32,
-        raycaster.render_rgb_flat(thread_count, is_debug),
-    )
-    .unwrap();
+    let region = if region.is_empty() {
+        None
+    } else {
+        let region = region.parse::<raycaster::Region>()?;
+        if !region.fits(width, height) {
+            return Err(format!(
+                "The region {:?} does not fit inside the {}x{} image",
+                region, width, height
+            )
+            .into());
+        }
+        Some(region)
+    };
+    // Place the rendered region back into the full image when saving
+    let placement = region
+        .filter(|_| is_full_size)
+        .map(|region| (region, width, height));
+
+    // Select the views to render and where to save each
+    let mut views: Vec<(camera::CameraDescription, path::PathBuf)> = if is_all_cameras {
+        if scene.cameras.is_empty() {
+            return Err("The scene does not have any 'cameras' to render from".into());
+        }
+        scene
+            .cameras
+            .iter()
+            .map(|(name, description)| (description.clone(), with_suffix(&output_path, name)))
+            .collect()
+    } else if !camera_name.is_empty() {
+        let description = scene.cameras.get(&camera_name).ok_or_else(|| {
+            format!(
+                "The camera '{}' is not found in the scene's 'cameras'",
+                camera_name
+            )
+        })?;
+        vec![(description.clone(), output_path)]
+    } else {
+        vec![(scene.camera.clone(), output_path)]
+    };
+
+    if is_framed {
+        match scene.bounding_box() {
+            Some(bounds) => {
+                for (description, _) in views.iter_mut() {
+                    *description = description.framed(&bounds, width as f32 / height as f32);
+                }
+            }
+            None => eprintln!("The scene has no bounded objects to frame; using the camera as is"),
+        }
+    }
+
+    // Render the scene as seen from the camera described in `description`,
+    // passing the image after each pass to `on_pass`
+    let render = |description: &camera::CameraDescription,
+                  on_pass: &mut dyn FnMut(usize, &raycaster::Frame)| {
+        let mut film = film::Film::from(description);
+        if let Some(tone_mapping) = tone_mapping {
+            film.tone_mapping = tone_mapping;
+        }
+        let raycaster = raycaster::Raycaster {
+            scene: sync::Arc::clone(&scene),
+            camera: camera::from_description(description, width, height).into(),
+            film,
+            settings,
+            region,
+        };
+        raycaster.render_progressive(thread_count, is_debug, passes, on_pass)
+    };
 
-    // Write to image file
+    for (description, output_path) in views {
+        let eyes = match &description.stereo {
+            Some(stereo) => {
+                let (left, right) = stereo.eyes(&description);
+                vec![left, right]
+            }
+            None => vec![description.clone()],
+        };
+
+        let mut frames = Vec::with_capacity(eyes.len());
+        for eye in &eyes {
+            // Write the image so far every once in a while so that a long
+            // render can be checked and stopped early
+            let mut last_write = time::Instant::now();
+            let mut on_pass = |pass: usize, frame: &raycaster::Frame| {
+                if pass == passes {
+                    return;
+                }
+                let is_time =
+                    write_seconds > 0.0 && last_write.elapsed().as_secs_f32() >= write_seconds;
+                // `is_multiple_of` would need a newer toolchain than the rest
+                #[allow(clippy::manual_is_multiple_of)]
+                let is_pass = write_passes > 0 && pass % write_passes == 0;
+                if passes > 1 && (is_time || is_pass) {
+                    let mut preview = frames.iter().collect::<Vec<_>>();
+                    preview.push(frame);
+                    println!("Writing pass {} to {}", pass, output_path.display());
+                    if let Err(e) = write(
+                        &preview,
+                        description.stereo.as_ref(),
+                        placement,
+                        None,
+                        &output_path,
+                    ) {
+                        eprintln!("Failed to write the image so far: {}", e);
+                    }
+                    last_write = time::Instant::now();
+                }
+            };
+            let mut frame = render(eye, &mut on_pass);
+            if is_denoise {
+                println!("Denoising");
+                frame.colors = denoise::Denoiser::default().denoise(&frame);
+                if !is_aovs {
+                    frame.surfaces = None;
+                }
+            }
+            frames.push(frame);
+        }
+        let frames = frames.iter().collect::<Vec<_>>();
+
+        save(
+            || {
+                write(
+                    &frames,
+                    description.stereo.as_ref(),
+                    placement,
+                    None,
+                    &output_path,
+                )
+            },
+            &output_path,
+        )?;
+        // OpenEXR files already have the passes as channels
+        if is_aovs && extension(&output_path).as_deref() != Some("exr") {
+            for &aov in aov::Aov::ALL.iter() {
+                let aov_path = with_suffix(&output_path, aov.name());
+                save(
+                    || {
+                        write(
+                            &frames,
+                            description.stereo.as_ref(),
+                            placement,
+                            Some(aov),
+                            &aov_path,
+                        )
+                    },
+                    &aov_path,
+                )?;
+            }
+        }
+        if is_heatmap {
+            // The sample counts are not colors so the heatmap is always 8-bit
+            let mut heatmap_path = with_suffix(&output_path, "samples");
+            if is_linear(&heatmap_path) {
+                heatmap_path.set_extension("png");
+            }
+            let heatmap = to_image(
+                &frames,
+                description.stereo.as_ref(),
+                placement,
+                raycaster::Frame::sample_heatmap,
+            );
+            save(|| heatmap.save(&heatmap_path), &heatmap_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Return the lowercase file extension of `path`.
+fn extension(path: &path::Path) -> Option<String> {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .map(str::to_lowercase)
+}
+
+/// Check if the file extension of `path` is of a format that stores linear
+/// colors of high dynamic range.
+fn is_linear(path: &path::Path) -> bool {
+    matches!(extension(path).as_deref(), Some("exr") | Some("hdr"))
+}
+
+/// Write the colors of the frames, or their pass `aov`, into a file in the
+/// format matching the extension of `path`: OpenEXR (`.exr`) and Radiance HDR
+/// (`.hdr`) get the linear values and other formats the values encoded into 8
+/// bits. OpenEXR files of the colors also get every rendered pass as more
+/// channels. Passes of an anaglyph are not mixed but kept apart: side by side
+/// in an image of one pass and in channels named by the eye in OpenEXR files
+/// of the colors.
+fn write(
+    frames: &[&raycaster::Frame],
+    stereo: Option<&stereo::Stereo>,
+    placement: Option<(raycaster::Region, usize, usize)>,
+    aov: Option<aov::Aov>,
+    path: &path::Path,
+) -> image::ImageResult<()> {
+    use exr::prelude::{AnyChannel, AnyChannels, FlatSamples, Image, WritableImage};
+
+    // Values of the passes mean nothing with the channels of the eyes mixed
+    let is_anaglyph = matches!(stereo, Some(x) if matches!(x.layout, stereo::Layout::Anaglyph));
+    let pass_stereo = stereo.map(|x| match x.layout {
+        stereo::Layout::Anaglyph => stereo::Stereo {
+            layout: stereo::Layout::SideBySide,
+            ..*x
+        },
+        _ => *x,
+    });
+
+    if !is_linear(path) {
+        let image = match aov {
+            Some(aov) => to_image(frames, pass_stereo.as_ref(), placement, |x| {
+                x.aov_rgb_flat(aov)
+            }),
+            None => to_image(frames, stereo, placement, raycaster::Frame::rgb_flat),
+        };
+        return image.save(path);
+    }
+
+    let linear = |aov: Option<aov::Aov>| match aov {
+        Some(aov) => to_image(frames, pass_stereo.as_ref(), placement, |x| {
+            x.aov_linear_flat(aov)
+        }),
+        None => to_image(frames, stereo, placement, raycaster::Frame::linear_rgb_flat),
+    };
+    let image = linear(aov);
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if extension(path).as_deref() == Some("exr") {
+        let mut passes = vec![(
+            "",
+            aov.map_or(&["R", "G", "B"][..], |x| x.channels()),
+            image,
+        )];
+        if aov.is_none() && frames.iter().all(|x| x.surfaces.is_some()) {
+            for &aov in aov::Aov::ALL.iter() {
+                if is_anaglyph {
+                    // Each eye gets channels of its own at the size of the
+                    // anaglyph
+                    for (eye, frame) in ["left.", "right."].iter().zip(frames) {
+                        let image = to_image(&[frame], None, placement, |x| x.aov_linear_flat(aov));
+                        passes.push((eye, aov.channels(), image));
+                    }
+                } else {
+                    passes.push(("", aov.channels(), linear(Some(aov))));
+                }
+            }
+        }
+        let channels = passes
+            .iter()
+            .flat_map(|(eye, names, image)| {
+                names.iter().enumerate().map(move |(i, &name)| {
+                    let samples = image.pixels().map(|x| x[i]).collect();
+                    AnyChannel::new(
+                        format!("{}{}", eye, name).as_str(),
+                        FlatSamples::F32(samples),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        Image::from_channels((width, height), AnyChannels::sort(channels.into()))
+            .write()
+            .to_file(path)
+            .map_err(|e| {
+                image::ImageError::Encoding(image::error::EncodingError::new(
+                    image::error::ImageFormatHint::Name("OpenEXR".to_string()),
+                    e,
+                ))
+            })
+    } else {
+        let file = io::BufWriter::new(fs::File::create(path)?);
+        let pixels = image.pixels().copied().collect::<Vec<_>>();
+        image::codecs::hdr::HdrEncoder::new(file).encode(&pixels, width, height)
+    }
+}
+
+/// Turn the frames into an image with `pixels`, combining the eyes of a stereo
+/// render. An eye without a frame yet is left black. With `placement` the
+/// frames are of a region that is placed into an image of the given width and
+/// height.
+fn to_image<T: Primitive + 'static>(
+    frames: &[&raycaster::Frame],
+    stereo: Option<&stereo::Stereo>,
+    placement: Option<(raycaster::Region, usize, usize)>,
+    pixels: impl Fn(&raycaster::Frame) -> Vec<T>,
+) -> ImageBuffer<Rgb<T>, Vec<T>> {
+    let mut images = frames.iter().map(|frame| {
+        let image =
+            ImageBuffer::from_vec(frame.width as u32, frame.height as u32, pixels(frame)).unwrap();
+        match placement {
+            Some((region, width, height)) => {
+                let mut full = ImageBuffer::new(width as u32, height as u32);
+                image::imageops::replace(&mut full, &image, region.x as u32, region.y as u32);
+                full
+            }
+            None => image,
+        }
+    });
+    let first = images.next().unwrap();
+    match stereo {
+        Some(stereo) => {
+            let second = images
+                .next()
+                .unwrap_or_else(|| ImageBuffer::new(first.width(), first.height()));
+            stereo.composite(&first, &second)
+        }
+        None => first,
+    }
+}
+
+/// Add `_<suffix>` to the end of the filename in `output_path`.
+fn with_suffix(output_path: &path::Path, suffix: &str) -> path::PathBuf {
+    let mut filename = output_path.file_stem().unwrap_or_default().to_os_string();
+    filename.push(format!("_{}", suffix));
+    if let Some(extension) = output_path.extension() {
+        filename.push(".");
+        filename.push(extension);
+    }
+    output_path.with_file_name(filename)
+}
+
+/// Write a file with `write` asking to retry if it fails.
+fn save(
+    write: impl Fn() -> image::ImageResult<()>,
+    output_path: &path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
     print!("\nSaving to {} ", output_path.display());
 
     // Saving could fail for example if a previous file is open; ask to retry
-    while let Err(e) = terminal_toys::spinner::start_spinner(|| image.save(&output_path)) {
+    while let Err(e) = terminal_toys::spinner::start_spinner(&write) {
         println!("There was an error saving the render: {}", e);
         let mut stdout = io::stdout();
         let _ = stdout.write(b"Try saving again? [Y/n]>");
@@ -169,3 +631,175 @@ pub fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raycast::{color::Color, vector::Vector3};
+
+    /// Frame of two pixels where the left one sees an object and the right
+    /// one nothing.
+    fn frame() -> raycaster::Frame {
+        raycaster::Frame {
+            width: 2,
+            height: 1,
+            colors: vec![Color::new(0.25, 0.5, 2.0), Color::new(1.5, 0.0, 0.125)],
+            sample_counts: vec![1; 2],
+            variances: vec![0.0; 2],
+            tone_mapping: film::ToneMapping::Clamp,
+            surfaces: Some(vec![
+                aov::Surface {
+                    depth: 3.0,
+                    normal: Vector3 {
+                        x: 0.0,
+                        y: 0.6,
+                        z: 0.8,
+                    },
+                    albedo: Color::new(0.5, 0.25, 1.0),
+                    position: Vector3 {
+                        x: 1.0,
+                        y: 2.0,
+                        z: -3.0,
+                    },
+                    object_id: 2,
+                },
+                aov::Surface::NONE,
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_write_exr_channels() {
+        let path = std::env::temp_dir().join(format!("raycast_test_{}.exr", std::process::id()));
+        let frame = frame();
+        write(&[&frame], None, None, None, &path).unwrap();
+        let image = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let channels = &image.layer_data[0].channel_data.list;
+        let expected: &[(&str, [f32; 2])] = &[
+            ("R", [0.25, 1.5]),
+            ("G", [0.5, 0.0]),
+            ("B", [2.0, 0.125]),
+            ("Z", [3.0, f32::INFINITY]),
+            ("N.X", [0.0, 0.0]),
+            ("N.Y", [0.6, 0.0]),
+            ("N.Z", [0.8, 0.0]),
+            ("albedo.R", [0.5, 0.0]),
+            ("albedo.G", [0.25, 0.0]),
+            ("albedo.B", [1.0, 0.0]),
+            ("P.X", [1.0, 0.0]),
+            ("P.Y", [2.0, 0.0]),
+            ("P.Z", [-3.0, 0.0]),
+            ("id", [2.0, 0.0]),
+        ];
+        assert_eq!(channels.len(), expected.len());
+        for (name, values) in expected {
+            let channel = channels
+                .iter()
+                .find(|x| x.name == **name)
+                .unwrap_or_else(|| panic!("No channel {}", name));
+            match &channel.sample_data {
+                exr::prelude::FlatSamples::F32(samples) => {
+                    assert_eq!(samples.as_slice(), &values[..], "{}", name)
+                }
+                _ => panic!("Channel {} is not of 32-bit floats", name),
+            }
+        }
+
+        // Without the passes only the colors are written
+        let mut frame = frame;
+        frame.surfaces = None;
+        write(&[&frame], None, None, None, &path).unwrap();
+        let image = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(image.layer_data[0].channel_data.list.len(), 3);
+    }
+
+    #[test]
+    fn test_anaglyph_passes_per_eye() {
+        let anaglyph = stereo::Stereo {
+            interocular_distance: 0.1,
+            convergence: None,
+            layout: stereo::Layout::Anaglyph,
+        };
+        let left = frame();
+        let mut right = frame();
+        right.surfaces.as_mut().unwrap()[0].depth = 5.0;
+
+        let path =
+            std::env::temp_dir().join(format!("raycast_test_{}_eyes.exr", std::process::id()));
+        write(&[&left, &right], Some(&anaglyph), None, None, &path).unwrap();
+        let image = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let channels = &image.layer_data[0].channel_data.list;
+        let depth = |name: &str| match &channels
+            .iter()
+            .find(|x| x.name == *name)
+            .unwrap()
+            .sample_data
+        {
+            exr::prelude::FlatSamples::F32(samples) => samples[0],
+            _ => panic!("Channel {} is not of 32-bit floats", name),
+        };
+        assert!(channels.iter().all(|x| x.name != *"Z"));
+        assert_eq!(depth("left.Z"), 3.0);
+        assert_eq!(depth("right.Z"), 5.0);
+        // Colors and both eyes' passes
+        assert_eq!(channels.len(), 3 + 2 * 11);
+
+        // An image of one pass has the eyes side by side
+        let path =
+            std::env::temp_dir().join(format!("raycast_test_{}_eyes.png", std::process::id()));
+        write(
+            &[&left, &right],
+            Some(&anaglyph),
+            None,
+            Some(aov::Aov::Normal),
+            &path,
+        )
+        .unwrap();
+        let image = image::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(image::GenericImageView::dimensions(&image), (4, 1));
+    }
+
+    #[test]
+    fn test_write_hdr() {
+        let path = std::env::temp_dir().join(format!("raycast_test_{}.hdr", std::process::id()));
+        write(&[&frame()], None, None, None, &path).unwrap();
+        let file = io::BufReader::new(fs::File::open(&path).unwrap());
+        let pixels = image::codecs::hdr::HdrDecoder::new(file)
+            .unwrap()
+            .read_image_hdr()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(pixels.len(), 2);
+        let expected = [[0.25, 0.5, 2.0], [1.5, 0.0, 0.125]];
+        for (pixel, expected) in pixels.iter().zip(expected.iter()) {
+            // Radiance HDR shares an exponent between the channels so they
+            // have 8 bits of precision relative to the brightest one
+            let tolerance = expected.iter().cloned().fold(0.0, f32::max) / 128.0;
+            for (x, y) in pixel.0.iter().zip(expected.iter()) {
+                assert!((x - y).abs() <= tolerance, "{:?} and {:?}", pixel, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_with_suffix() {
+        assert_eq!(
+            with_suffix(path::Path::new("renders/out.png"), "front"),
+            path::Path::new("renders/out_front.png")
+        );
+        assert_eq!(
+            with_suffix(path::Path::new("out"), "left"),
+            path::Path::new("out_left")
+        );
+        assert_eq!(
+            with_suffix(path::Path::new("a.b/out.tar.exr"), "1"),
+            path::Path::new("a.b/out.tar_1.exr")
+        );
+    }
+}
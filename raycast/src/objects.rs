@@ -1,6 +1,8 @@
 use crate::{
+    bounds::BoundingBox,
     matrix,
     ray::Ray,
+    transform::Transform,
     vector::{UnitVector3, Vector3, Vector4},
     Intersect, Intersection, Material,
 };
@@ -8,32 +10,125 @@ use crate::{
 #[derive(Debug)]
 pub struct Object3D {
     transform: Option<matrix::SquareMatrix4>,
+    /// The transform before inversing, needed for blending it when moving
+    start: Option<Transform>,
+    motion: Option<Motion>,
     object: Vec<Shape>,
     material: Material,
 }
 
+/// How an object moves while the shutter is open.
+#[derive(Debug)]
+pub struct Motion {
+    /// Transform at the closing of the shutter
+    pub end: Option<Transform>,
+    /// Distance travelled in world space during the time the shutter is open
+    pub velocity: Option<Vector3>,
+}
+
+impl Motion {
+    /// Check if the object stays at `start` the whole time.
+    fn is_still(&self, start: Option<&Transform>) -> bool {
+        let is_resting = match self.velocity {
+            Some(v) => v.x == 0.0 && v.y == 0.0 && v.z == 0.0,
+            None => true,
+        };
+        let is_same_end = match &self.end {
+            Some(end) => {
+                let start = start.map_or_else(matrix::SquareMatrix4::identity, Transform::matrix);
+                end.matrix().0 == start.0
+            }
+            None => true,
+        };
+        is_resting && is_same_end
+    }
+}
+
+/// Where an object is at one moment while the shutter is open, ie. what moves
+/// rays into its object space.
+pub(crate) enum Placement {
+    /// The object stays put so its transform inversed in advance is used
+    Still,
+    /// Inverse of the transform at the moment
+    Moved(matrix::SquareMatrix4),
+    /// The object is flattened at the moment, eg. scaled through zero, and
+    /// cannot be hit
+    Flat,
+}
+
 // TODO This would require less memory (ie. not copying Object3D::Composites)
 // if `object` was an Rc<Object3D>?
 impl Object3D {
     pub fn new(
-        transform: Option<matrix::SquareMatrix4>,
+        transform: Option<Transform>,
+        motion: Option<Motion>,
         object: Vec<Shape>,
         material: Option<Material>,
     ) -> Self {
+        // Motion that does not change the transform is the same as standing
+        // still, which saves inversing the matrix for every ray
+        let motion = motion.filter(|m| !m.is_still(transform.as_ref()));
         Self {
             // Inverse transform here in advance, because always used so
-            transform: transform.map(|t| {
-                t.inversed()
-                    .unwrap_or_else(|| panic!("The matrix does not have an inverse: {}", t))
-            }),
+            transform: transform.as_ref().map(|t| inversed(t.matrix())),
+            start: transform,
+            motion,
             object,
             material: material.unwrap_or_default(),
         }
     }
-}
 
-impl Intersect for Object3D {
-    fn intersect(&self, ray: &Ray, tmin: f32) -> Option<Intersection> {
+    /// Return the transform of a moving object at `time` in [0, 1].
+    fn transform_at(&self, time: f32) -> Option<matrix::SquareMatrix4> {
+        let motion = self.motion.as_ref()?;
+        let mut m = match (&self.start, &motion.end) {
+            (Some(start), Some(end)) => start.lerp(end, time),
+            (None, Some(end)) => Transform::default().lerp(end, time),
+            (Some(start), None) => start.matrix(),
+            (None, None) => matrix::SquareMatrix4::identity(),
+        };
+        if let Some(velocity) = motion.velocity {
+            let offset = matrix::SquareMatrix4::translation(Vector4::from_v3(velocity * time, 1.0));
+            m = &offset * &m;
+        }
+        Some(m)
+    }
+
+    /// Move `point` from the object's space to world space at `time` in
+    /// [0, 1], eg. for a point of intersection.
+    pub(crate) fn to_world(&self, point: Vector3, time: f32) -> Vector3 {
+        let m = match (self.transform_at(time), &self.start) {
+            (Some(m), _) => m,
+            (None, Some(start)) => start.matrix(),
+            (None, None) => return point,
+        };
+        // Uniform scaling scales `w` too, which is dropped when moving rays
+        // into object space, so undo that to come back to where they were
+        (&m * &Vector4::from_v3(point, 1.0 / m.0[3][3])).xyz()
+    }
+
+    /// Return where the object is at `time` in [0, 1] for intersecting rays
+    /// shot at that moment.
+    pub(crate) fn placement_at(&self, time: f32) -> Placement {
+        match self.transform_at(time) {
+            Some(m) => m.inversed().map_or(Placement::Flat, Placement::Moved),
+            None => Placement::Still,
+        }
+    }
+
+    /// Intersect the object placed at `placement`, which is from
+    /// `placement_at` with the time of `ray`.
+    pub(crate) fn intersect_placed(
+        &self,
+        ray: &Ray,
+        tmin: f32,
+        placement: &Placement,
+    ) -> Option<Intersection> {
+        let inverse = match placement {
+            Placement::Still => self.transform.as_ref(),
+            Placement::Moved(m) => Some(m),
+            Placement::Flat => return None,
+        };
         // Helper to reduce code duplication
         let get_intersection = |r| {
             self.object
@@ -58,8 +153,8 @@ impl Intersect for Object3D {
                 .reduce(|acc, x| if x.t < acc.t { x } else { acc })
         };
 
-        if let Some(t) = &self.transform {
-            let ray = Ray::with_transform(ray.origin, ray.direction, t);
+        if let Some(t) = inverse {
+            let ray = Ray::with_transform(ray.origin, ray.direction, ray.time, t);
             get_intersection(&ray)
                 // If there was an intersection transform its normal to object
                 // space
@@ -74,6 +169,48 @@ impl Intersect for Object3D {
             get_intersection(ray)
         }
     }
+
+    /// Return a box in world space that contains the object or `None` if the
+    /// object is unbounded, eg. a plane.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let local = self
+            .object
+            .iter()
+            .map(Shape::bounding_box)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .reduce(|acc, x| acc.union(&x))?;
+
+        if self.motion.is_some() {
+            // Cover the places the object passes through while moving
+            (0..=MOTION_BOUNDS_STEPS)
+                .map(|i| {
+                    let time = i as f32 / MOTION_BOUNDS_STEPS as f32;
+                    local.transformed(&self.transform_at(time).unwrap())
+                })
+                .reduce(|acc, x| acc.union(&x))
+        } else if let Some(start) = &self.start {
+            Some(local.transformed(&start.matrix()))
+        } else {
+            Some(local)
+        }
+    }
+}
+
+/// Amount of steps along the path of a moving object at which its bounding box
+/// is taken
+const MOTION_BOUNDS_STEPS: usize = 8;
+
+fn inversed(m: matrix::SquareMatrix4) -> matrix::SquareMatrix4 {
+    m.inversed()
+        .unwrap_or_else(|| panic!("The matrix does not have an inverse: {}", m))
+}
+
+impl Intersect for Object3D {
+    fn intersect(&self, ray: &Ray, tmin: f32) -> Option<Intersection> {
+        // Moving objects are placed according to the moment the ray is shot at
+        self.intersect_placed(ray, tmin, &self.placement_at(ray.time))
+    }
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -97,6 +234,42 @@ pub enum Shape {
     },
 }
 
+impl Shape {
+    /// Return a box that contains the shape or `None` if the shape is
+    /// unbounded.
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let around = |center: Vector3, extent: Vector3| BoundingBox {
+            min: center - extent,
+            max: center + extent,
+        };
+        match *self {
+            Shape::Sphere { origin, radius } => Some(around(
+                origin,
+                Vector3 {
+                    x: radius,
+                    y: radius,
+                    z: radius,
+                },
+            )),
+            Shape::Plane { .. } => None,
+            Shape::Triangle { vertices, .. } => BoundingBox::from_points(vertices.iter().copied()),
+            // The torus lies on the xy-plane
+            Shape::Torus {
+                origin,
+                inner_radius,
+                tube_radius,
+            } => Some(around(
+                origin,
+                Vector3 {
+                    x: inner_radius + tube_radius,
+                    y: inner_radius + tube_radius,
+                    z: tube_radius,
+                },
+            )),
+        }
+    }
+}
+
 fn sphere_intersect(
     origin: Vector3,
     radius: f32,
@@ -444,6 +617,84 @@ fn min_greater_than(tmin: f32, ts: &[f32]) -> Option<f32> {
         .and_then(|x| if tmin < *x { Some(*x) } else { None })
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn unit_sphere(transform: &str, motion: Option<Motion>) -> Object3D {
+        Object3D::new(
+            Some(Transform::try_from(transform).unwrap()),
+            motion,
+            vec![Shape::Sphere {
+                origin: Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                radius: 1.0,
+            }],
+            None,
+        )
+    }
+
+    fn ray_at(time: f32) -> Ray {
+        Ray {
+            origin: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 5.0,
+            },
+            direction: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            }
+            .normalized(),
+            time,
+        }
+    }
+
+    #[test]
+    fn test_flat_moment_is_not_hit() {
+        // Scaling from 1 to -1 passes through 0 halfway
+        let sphere = unit_sphere(
+            "Scale 1",
+            Some(Motion {
+                end: Some(Transform::try_from("Scale -1").unwrap()),
+                velocity: None,
+            }),
+        );
+        assert!(matches!(sphere.placement_at(0.5), Placement::Flat));
+        assert!(sphere.intersect(&ray_at(0.5), f32::EPSILON).is_none());
+        assert!(sphere.intersect(&ray_at(0.0), f32::EPSILON).is_some());
+    }
+
+    #[test]
+    fn test_motion_without_change_is_still() {
+        let sphere = unit_sphere(
+            "Translate 0 1 0",
+            Some(Motion {
+                end: Some(Transform::try_from("Translate 0 1 0").unwrap()),
+                velocity: None,
+            }),
+        );
+        assert!(matches!(sphere.placement_at(0.5), Placement::Still));
+        let moving = unit_sphere(
+            "Translate 0 1 0",
+            Some(Motion {
+                end: None,
+                velocity: Some(Vector3 {
+                    x: 1.0,
+                    y: 0.0,
+                    z: 0.0,
+                }),
+            }),
+        );
+        assert!(matches!(moving.placement_at(0.5), Placement::Moved(_)));
+    }
+}
+
 #[cfg(test)]
 /// Kudos:
 /// https://jwilson.coe.uga.edu/EMAT6680Fa09/Davenport/Solving%20Quartic%20Equations.pdf
@@ -7,6 +7,9 @@ use crate::{
 pub struct Ray {
     pub origin: Vector3,
     pub direction: UnitVector3,
+    /// Moment in [0, 1] between the opening and closing of the shutter at
+    /// which the ray travels
+    pub time: f32,
 }
 impl Ray {
     pub fn cast(&self, t: f32) -> Vector3 {
@@ -16,6 +19,7 @@ impl Ray {
     pub fn with_transform(
         mut origin: Vector3,
         mut direction: UnitVector3,
+        time: f32,
         m: &SquareMatrix4,
     ) -> Self {
         origin = (m * &Vector4::from_v3(origin, 1.0)).xyz();
@@ -26,6 +30,10 @@ impl Ray {
             .xyz()
             .normalized();
 
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 }
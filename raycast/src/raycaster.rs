@@ -1,122 +1,646 @@
-use std::sync;
+use std::ops;
+use std::str;
+use std::sync::{self, atomic};
 
 use terminal_toys as tt;
 
-use crate::{camera, color, scene};
+use crate::{aov, camera, color, film, sampler, scene};
 
-const AA_ITERATION_COUNT: usize = 25;
+/// The `render` section of a scene file.
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+pub struct RenderSettings {
+    /// Amount of rays shot through each pixel
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+    /// Amount of times a ray is traced further after hitting something
+    #[serde(default = "default_depth")]
+    pub depth: usize,
+    /// Renders with the same seed come out the same
+    #[serde(default)]
+    pub seed: u64,
+    /// How the rays are spread inside each pixel
+    #[serde(default)]
+    pub pattern: sampler::SamplePattern,
+    /// Stop sampling a pixel once the standard error of its mean luminance
+    /// falls below this fraction of the luminance. Then `samples` is the most
+    /// that are taken.
+    #[serde(default)]
+    pub noise_threshold: Option<f32>,
+    /// Amount of samples taken before checking if a pixel has converged
+    #[serde(default = "default_min_samples")]
+    pub min_samples: usize,
+    /// Also render the surfaces seen in each pixel for the passes in `aov`
+    #[serde(default)]
+    pub aovs: bool,
+}
+
+fn default_samples() -> usize {
+    25
+}
+
+fn default_depth() -> usize {
+    6
+}
+
+fn default_min_samples() -> usize {
+    16
+}
 
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            samples: default_samples(),
+            depth: default_depth(),
+            seed: 0,
+            pattern: sampler::SamplePattern::default(),
+            noise_threshold: None,
+            min_samples: default_min_samples(),
+            aovs: false,
+        }
+    }
+}
+
+/// The scene and camera are shared so that the same scene can be rendered
+/// from many views, eg. one for each eye.
 pub struct Raycaster {
-    pub scene: scene::Scene,
-    pub camera: camera::PerspectiveCamera,
+    pub scene: sync::Arc<scene::Scene>,
+    pub camera: sync::Arc<dyn camera::Camera>,
+    pub film: film::Film,
+    pub settings: RenderSettings,
+    /// Part of the image to render; the whole image when `None`
+    pub region: Option<Region>,
+}
+
+/// Rectangle of pixels inside the image with the origin at the top left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// Check that the region is not empty and fits inside an image of
+    /// `width` by `height` pixels.
+    // `is_some_and` would need a newer toolchain than the rest
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn fits(&self, width: usize, height: usize) -> bool {
+        // Huge values from the command line must not overflow
+        self.width > 0
+            && self.height > 0
+            && self
+                .x
+                .checked_add(self.width)
+                .map_or(false, |end| end <= width)
+            && self
+                .y
+                .checked_add(self.height)
+                .map_or(false, |end| end <= height)
+    }
+}
+
+impl str::FromStr for Region {
+    type Err = String;
+
+    /// Parse a region from the format `x,y,width,height`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|x| x.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed parsing region '{}': {}", s, e))?;
+        match values[..] {
+            [x, y, width, height] => Ok(Region {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!(
+                "Region '{}' is not in the format 'x,y,width,height'",
+                s
+            )),
+        }
+    }
+}
+
+/// A rendered image or a region of one.
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Linear colors of the pixels row by row from the top left with the
+    /// camera's exposure and white balance applied
+    pub colors: Vec<color::Color>,
+    /// Amount of samples taken in each pixel
+    pub sample_counts: Vec<usize>,
+    /// Variance of the mean luminance of the developed samples in each pixel,
+    /// ie. how noisy `colors` still is, or zero where less than two samples
+    /// are taken
+    pub variances: Vec<f32>,
+    /// Curve applied to the colors when encoding them into bytes
+    pub tone_mapping: film::ToneMapping,
+    /// Surfaces seen in the pixels if rendered with `aovs` on
+    pub surfaces: Option<Vec<aov::Surface>>,
+}
+
+impl Frame {
+    /// Return the linear colors as a one-dimensional array of floats (i.e.,
+    /// three (3) floats per pixel).
+    pub fn linear_rgb_flat(&self) -> Vec<f32> {
+        self.colors
+            .iter()
+            .flat_map(|c| [c.r(), c.g(), c.b()])
+            .collect()
+    }
+
+    /// Return the colors tone mapped and encoded in sRGB as a one-dimensional
+    /// array of RGB-bytes (i.e., three (3) bytes per pixel).
+    pub fn rgb_flat(&self) -> Vec<u8> {
+        self.colors
+            .iter()
+            .flat_map(|&c| <[u8; 3]>::from(self.tone_mapping.apply(c)))
+            .collect()
+    }
+
+    /// Return the pass `aov` as a one-dimensional array of floats (i.e.,
+    /// three (3) floats per pixel).
+    ///
+    /// # Panics:
+    /// This panics if the frame is rendered without `aovs`
+    pub fn aov_linear_flat(&self, aov: aov::Aov) -> Vec<f32> {
+        aov.linear_flat(self.surfaces.as_ref().expect("No AOVs rendered"))
+    }
+
+    /// Return the pass `aov` as a one-dimensional array of RGB-bytes (i.e.,
+    /// three (3) bytes per pixel).
+    ///
+    /// # Panics:
+    /// This panics if the frame is rendered without `aovs`
+    pub fn aov_rgb_flat(&self, aov: aov::Aov) -> Vec<u8> {
+        aov.rgb_flat(self.surfaces.as_ref().expect("No AOVs rendered"))
+    }
+
+    /// Return the amount of samples in each pixel as RGB-bytes going from
+    /// blue for the fewest to red for the most samples.
+    pub fn sample_heatmap(&self) -> Vec<u8> {
+        const RAMP: [[f32; 3]; 4] = [
+            [0.0, 0.0, 255.0],
+            [0.0, 255.0, 0.0],
+            [255.0, 255.0, 0.0],
+            [255.0, 0.0, 0.0],
+        ];
+        let max = self.sample_counts.iter().copied().max().unwrap_or(0).max(1);
+        self.sample_counts
+            .iter()
+            .flat_map(|&n| {
+                let t = n as f32 / max as f32 * (RAMP.len() - 1) as f32;
+                let i = (t as usize).min(RAMP.len() - 2);
+                let f = t - i as f32;
+                let mut rgb = [0; 3];
+                for (c, (a, b)) in rgb.iter_mut().zip(RAMP[i].iter().zip(RAMP[i + 1].iter())) {
+                    *c = (a + (b - a) * f) as u8;
+                }
+                rgb
+            })
+            .collect()
+    }
 }
 
 impl Raycaster {
     /// Render the scene into a one-dimensional array of RGB-bytes (i.e., three
     /// (3) bytes per pixel) using `thread_count` concurrent threads.
-    pub fn render_rgb_flat(self, thread_count: usize, is_debug: bool) -> Vec<u8> {
-        let (width, height) = self.camera.image_dimensions();
-        let segment_height = height / thread_count;
+    pub fn render_rgb_flat(&self, thread_count: usize, is_debug: bool) -> Vec<u8> {
+        self.render(thread_count, is_debug).rgb_flat()
+    }
+
+    /// Render the scene using `thread_count` concurrent threads.
+    pub fn render(&self, thread_count: usize, is_debug: bool) -> Frame {
+        self.render_progressive(thread_count, is_debug, 1, |_, _| ())
+    }
+
+    /// Render the scene in `passes` passes that each take more samples in
+    /// every pixel. After each pass `on_pass` is called with the number of
+    /// the pass and the image rendered so far. There are no more passes than
+    /// samples (see `pass_samples`).
+    pub fn render_progressive(
+        &self,
+        thread_count: usize,
+        is_debug: bool,
+        passes: usize,
+        mut on_pass: impl FnMut(usize, &Frame),
+    ) -> Frame {
+        let region = self.region();
+        let passes = pass_samples(self.settings.samples, passes);
+
+        // The samples of the pixels are kept in the tiles between the passes
+        let tiles = sync::Arc::new(
+            tiles(&region)
+                .into_iter()
+                .map(|tile| {
+                    let pixels = vec![Pixel::default(); tile.x.len() * tile.y.len()];
+                    (tile, sync::Mutex::new(pixels))
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let mut frame = Frame {
+            width: region.width,
+            height: region.height,
+            colors: vec![color::consts::BLACK; region.width * region.height],
+            sample_counts: vec![0; region.width * region.height],
+            variances: vec![0.0; region.width * region.height],
+            // The normals of debug coloring are shown as is
+            tone_mapping: if is_debug {
+                film::ToneMapping::Clamp
+            } else {
+                self.film.tone_mapping
+            },
+            surfaces: if self.settings.aovs {
+                Some(vec![aov::Surface::NONE; region.width * region.height])
+            } else {
+                None
+            },
+        };
+
+        let is_single_pass = passes.len() == 1;
+        for (pass, samples) in passes.into_iter().enumerate() {
+            self.render_pass(thread_count, is_debug, &tiles, samples, is_single_pass);
+
+            // Place the rendered tiles into the image
+            for (tile, pixels) in tiles.iter() {
+                let pixels = pixels.lock().unwrap();
+                for (row, iy) in pixels.chunks(tile.x.len()).zip(tile.y.clone()) {
+                    let start = (iy - region.y) * region.width + tile.x.start - region.x;
+                    for (i, pixel) in row.iter().enumerate() {
+                        let color = pixel.color();
+                        // The normals of debug coloring are shown as is
+                        frame.colors[start + i] = if is_debug {
+                            color
+                        } else {
+                            self.film.develop(color)
+                        };
+                        frame.sample_counts[start + i] = pixel.variance.count;
+                        frame.variances[start + i] =
+                            pixel.developed.variance_of_mean().unwrap_or(0.0);
+                        if let Some(surfaces) = frame.surfaces.as_mut() {
+                            surfaces[start + i] = pixel.surface.surface();
+                        }
+                    }
+                }
+            }
+            on_pass(pass + 1, &frame);
+        }
+
+        frame
+    }
+
+    /// Take the samples in the range `samples` for every pixel in `tiles`.
+    fn render_pass(
+        &self,
+        thread_count: usize,
+        is_debug: bool,
+        tiles: &sync::Arc<PixelTiles>,
+        samples: ops::Range<usize>,
+        show_progress: bool,
+    ) {
+        let region = self.region();
         let mut img_threads = Vec::with_capacity(thread_count);
 
-        println!("Rendering:");
+        // The threads take the next unrendered tile from the shared list until
+        // there are none left, so that all stay busy even if some parts of
+        // the image are slower to render
+        let next_tile = sync::Arc::new(atomic::AtomicUsize::new(0));
 
-        let camera = { sync::Arc::new(self.camera) };
-        let scene = { sync::Arc::new(self.scene) };
+        // Progress bars of every pass would fill the terminal so progressive
+        // renders only tell which pass is going on
+        let progress_bars: Vec<Option<_>> = if show_progress {
+            println!("Rendering:");
+            tt::ProgressBar::multiple(region.width * region.height, 25, thread_count)
+                .into_iter()
+                .map(Some)
+                .collect()
+        } else {
+            println!("Rendering samples {}-{}", samples.start + 1, samples.end);
+            (0..thread_count).map(|_| None).collect()
+        };
 
         // Spawn the threads to render in
-        for (i, mut progress_bar) in tt::ProgressBar::multiple(width * height, 25, thread_count)
-            .into_iter()
-            .enumerate()
-        {
-            let arc_camera = sync::Arc::clone(&camera);
-            let arc_scene = sync::Arc::clone(&scene);
-
-            // Every pixel in segment counts towards progress
-            progress_bar.title(&format!("  Thread #{} progress", i + 1));
-
-            let y_range = {
-                // Iterate the coordinates in image segments
-                let start = i * segment_height;
-                let mut end = start + segment_height;
-                // The last created thread takes the remaining rows as well
-                if i == thread_count - 1 {
-                    end += height % thread_count;
-                }
-                start..end
-            };
+        for (i, mut progress_bar) in progress_bars.into_iter().enumerate() {
+            let arc_camera = sync::Arc::clone(&self.camera);
+            let arc_scene = sync::Arc::clone(&self.scene);
+            let settings = self.settings;
+            let film = self.film;
+            let tiles = sync::Arc::clone(tiles);
+            let next_tile = sync::Arc::clone(&next_tile);
+            let samples = samples.clone();
 
-            let mut img_vec = Vec::with_capacity(width * y_range.len());
+            // Every pixel rendered by the thread counts towards progress
+            if let Some(progress_bar) = progress_bar.as_mut() {
+                progress_bar.title(&format!("  Thread #{} progress", i + 1));
+            }
 
             img_threads.push(std::thread::spawn(move || {
-                for iy in y_range {
-                    for ix in 0..width {
-                        img_vec.extend_from_slice(&shade_pixel(
-                            ix,
-                            iy,
-                            width,
-                            height,
+                while let Some((tile, pixels)) =
+                    tiles.get(next_tile.fetch_add(1, atomic::Ordering::Relaxed))
+                {
+                    let mut pixels = pixels.lock().unwrap();
+                    let coordinates = tile
+                        .y
+                        .clone()
+                        .flat_map(|iy| tile.x.clone().map(move |ix| (ix, iy)));
+                    for (coordinate, pixel) in coordinates.zip(pixels.iter_mut()) {
+                        shade_pixel(
+                            coordinate,
                             &arc_camera,
                             &arc_scene,
+                            &settings,
+                            &film,
+                            samples.clone(),
+                            pixel,
                             is_debug,
-                        ));
-                        progress_bar.lap().expect("Progress bar print failure");
+                        );
+                        if let Some(progress_bar) = progress_bar.as_mut() {
+                            progress_bar.lap().expect("Progress bar print failure");
+                        }
                     }
                 }
-                // Return the rendered pixels in segment
-                img_vec
             }));
         }
 
-        // Wait for rendering threads to finish and combine the rendered segments
-        // in order
-        let mut img_combined = Vec::with_capacity(width * height);
+        // Wait for rendering threads to finish
         for t in img_threads {
-            img_combined.append(&mut t.join().unwrap());
+            t.join().unwrap();
+        }
+
+        if show_progress {
+            // Move command line cursor to bottom of progress bars
+            print!("\x1b[{}B", thread_count);
         }
+    }
 
-        // Move command line cursor to bottom of progress bars
-        print!("\x1b[{}B", thread_count);
+    /// Return the part of the image that is rendered.
+    fn region(&self) -> Region {
+        let (width, height) = self.camera.image_dimensions();
+        self.region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        })
+    }
+}
 
-        img_combined
+/// Split the `samples` taken in every pixel into the ranges of samples taken
+/// in each of `passes` passes. There are at least one and at most `samples`
+/// passes so that every pass takes a sample.
+pub fn pass_samples(samples: usize, passes: usize) -> Vec<ops::Range<usize>> {
+    let passes = passes.clamp(1, samples.max(1));
+    (0..passes)
+        .map(|pass| pass * samples / passes..(pass + 1) * samples / passes)
+        .collect()
+}
+
+/// Width and height in pixels of the tiles that the image is rendered in
+const TILE_SIZE: usize = 16;
+
+/// Rectangle of pixels rendered by one thread at a time.
+#[derive(Clone, Debug)]
+struct Tile {
+    x: ops::Range<usize>,
+    y: ops::Range<usize>,
+}
+
+/// Split the region into tiles going row by row from the top left.
+fn tiles(region: &Region) -> Vec<Tile> {
+    let (right, bottom) = (region.x + region.width, region.y + region.height);
+    let mut tiles = Vec::new();
+    for y in (region.y..bottom).step_by(TILE_SIZE) {
+        for x in (region.x..right).step_by(TILE_SIZE) {
+            tiles.push(Tile {
+                x: x..(x + TILE_SIZE).min(right),
+                y: y..(y + TILE_SIZE).min(bottom),
+            });
+        }
     }
+    tiles
 }
 
+/// The tiles of an image with the pixels inside each.
+type PixelTiles = Vec<(Tile, sync::Mutex<Vec<Pixel>>)>;
+
+/// Samples taken so far in one pixel.
+#[derive(Clone, Copy)]
+struct Pixel {
+    sum: color::Color,
+    variance: RunningVariance,
+    /// Luminance of the samples as they look in the image
+    developed: RunningVariance,
+    surface: aov::SurfaceSum,
+}
+
+impl Default for Pixel {
+    fn default() -> Self {
+        Pixel {
+            sum: color::consts::BLACK,
+            variance: RunningVariance::default(),
+            developed: RunningVariance::default(),
+            surface: aov::SurfaceSum::default(),
+        }
+    }
+}
+
+impl Pixel {
+    /// Average of the samples.
+    fn color(&self) -> color::Color {
+        self.sum * (1.0 / self.variance.count.max(1) as f32)
+    }
+}
+
+/// Add the samples numbered in `samples` into `pixel` unless it has already
+/// converged.
+#[allow(clippy::too_many_arguments)]
 fn shade_pixel(
-    ix: usize,
-    iy: usize,
-    width: usize,
-    height: usize,
-    camera: &sync::Arc<camera::PerspectiveCamera>,
+    (ix, iy): (usize, usize),
+    camera: &sync::Arc<dyn camera::Camera>,
     scene: &sync::Arc<scene::Scene>,
+    settings: &RenderSettings,
+    film: &film::Film,
+    samples: ops::Range<usize>,
+    pixel: &mut Pixel,
     debug: bool,
-) -> [u8; 3] {
-    let mut color = color::consts::BLACK;
+) {
+    let (width, height) = camera.image_dimensions();
+    let mut sampler = sampler::PixelSampler::new(
+        settings.seed,
+        iy * width + ix,
+        settings.pattern,
+        settings.samples,
+    );
 
     // Anti-aliasing: sample each pixel in some pattern and return average
     // TODO Separate AA into a general function; AA works on a pixel:
     // 1) get location and size of a pixel (input: rectangle)
     // 2) shoot rays into these bounds (output: coordinates)
-    for _ in 0..AA_ITERATION_COUNT {
-        let (tx, ty) = (rand::random::<f32>(), rand::random::<f32>());
+    for i in samples {
+        // Adaptive sampling: Stop when more samples would not change the
+        // pixel noticeably
+        match settings.noise_threshold {
+            Some(threshold)
+                if pixel.variance.count >= settings.min_samples
+                    && pixel.variance.is_converged(threshold) =>
+            {
+                break
+            }
+            _ => (),
+        }
+
+        let (tx, ty) = sampler.pixel_offset(i);
         // Calculate image plane coordinates x,y so that they're in [-1, 1]
         let x: f32 = (ix as f32 + tx) / width as f32 * 2.0 - 1.0;
         // y is negated to transform from raster-space (ie. origin top left)
         // into screen-space (origin bottom left)
         let y: f32 = -((iy as f32 + ty) / height as f32 * 2.0 - 1.0);
 
-        let ray = camera.shoot_at(x, y);
+        let ray = camera.shoot_at(x, y, &mut sampler.rng);
+        if let Some(ray) = ray.as_ref().filter(|_| settings.aovs) {
+            pixel.surface.add(ray, scene.first_hit(ray));
+        }
 
-        if debug {
-            color += &scene.color_debug(&ray);
-        } else {
-            // Shade the pixel with RGB color; 6 traces/reflections are made for
-            // each intersection
-            color += &scene.trace(&ray, 6);
+        // Parts of the image outside the projection stay black
+        let sample = match ray {
+            Some(ray) if debug => scene.color_debug(&ray),
+            // Shade the pixel with RGB color; `depth` traces/reflections are
+            // made for each intersection
+            Some(ray) => scene.trace(&ray, settings.depth, &mut sampler.rng),
+            None => color::consts::BLACK,
+        };
+        pixel.sum += &sample;
+        pixel.variance.add(sample.luminance());
+        // The film weighs the channels differently, so the noise seen in the
+        // image is measured after developing each sample
+        let developed = if debug { sample } else { film.develop(sample) };
+        pixel.developed.add(developed.luminance());
+    }
+}
+
+/// Welford's online algorithm for the mean and variance of a sequence.
+#[derive(Clone, Copy, Default)]
+struct RunningVariance {
+    count: usize,
+    mean: f32,
+    /// Sum of squared differences from the mean
+    m2: f32,
+}
+
+impl RunningVariance {
+    fn add(&mut self, x: f32) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Check if the standard error of the mean is at most `threshold` times
+    /// the mean. Very dark pixels are compared to a small minimum brightness
+    /// instead so that they do not need endless samples.
+    fn is_converged(&self, threshold: f32) -> bool {
+        match self.variance_of_mean() {
+            Some(variance) => {
+                let standard_error = variance.sqrt();
+                standard_error <= threshold * self.mean.max(MIN_CONVERGED_LUMINANCE)
+            }
+            None => false,
+        }
+    }
+
+    /// Return the variance of the mean of the sequence if there are enough
+    /// values to estimate it.
+    fn variance_of_mean(&self) -> Option<f32> {
+        if self.count < 2 {
+            return None;
         }
+        let variance = self.m2 / (self.count - 1) as f32;
+        Some(variance / self.count as f32)
     }
+}
 
-    color *= 1.0 / AA_ITERATION_COUNT as f32;
+/// Luminance under which pixels count as black when checking for convergence
+const MIN_CONVERGED_LUMINANCE: f32 = 1.0 / 256.0;
 
-    color.into()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiles_cover_region_once() {
+        let (width, height) = (TILE_SIZE * 4, TILE_SIZE * 2);
+        let region = Region {
+            x: 3,
+            y: 2,
+            width: TILE_SIZE * 3 + 5,
+            height: TILE_SIZE + 1,
+        };
+        let mut covered = vec![0; width * height];
+        for tile in tiles(&region) {
+            for iy in tile.y.clone() {
+                for ix in tile.x.clone() {
+                    covered[iy * width + ix] += 1;
+                }
+            }
+        }
+        for (i, &n) in covered.iter().enumerate() {
+            let (ix, iy) = (i % width, i / width);
+            let is_inside = (region.x..region.x + region.width).contains(&ix)
+                && (region.y..region.y + region.height).contains(&iy);
+            assert_eq!(n, if is_inside { 1 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn test_parse_region() {
+        let region: Region = "10, 20,30,40".parse().unwrap();
+        assert_eq!(
+            region,
+            Region {
+                x: 10,
+                y: 20,
+                width: 30,
+                height: 40
+            }
+        );
+        assert!(region.fits(40, 60));
+        assert!(!region.fits(39, 60));
+        assert!(!"18446744073709551615,0,2,2"
+            .parse::<Region>()
+            .unwrap()
+            .fits(40, 60));
+        assert!("1,2,3".parse::<Region>().is_err());
+        assert!("1,2,3,x".parse::<Region>().is_err());
+    }
+
+    #[test]
+    fn test_pass_samples() {
+        // Every sample is taken once and the passes take about as many
+        assert_eq!(pass_samples(10, 3), vec![0..3, 3..6, 6..10]);
+        assert_eq!(pass_samples(4, 4), vec![0..1, 1..2, 2..3, 3..4]);
+        // Passes without samples are left out
+        assert_eq!(pass_samples(2, 5), vec![0..1, 1..2]);
+        assert_eq!(pass_samples(8, 0), vec![0..8]);
+        assert_eq!(pass_samples(0, 3), vec![0..0]);
+    }
+
+    #[test]
+    fn test_running_variance() {
+        let mut variance = RunningVariance::default();
+        for &x in &[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            variance.add(x);
+        }
+        assert!((variance.mean - 5.0).abs() < 1e-6);
+        // Sample variance is 32 / 7
+        assert!((variance.m2 - 32.0).abs() < 1e-4);
+        // Standard error is sqrt(32 / 7 / 8) = 0.756
+        assert!(variance.is_converged(0.16));
+        assert!(!variance.is_converged(0.14));
+    }
 }
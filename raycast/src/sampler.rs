@@ -0,0 +1,151 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+/// Select how the points inside a pixel are picked for anti-aliasing.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub enum SamplePattern {
+    /// Independent uniformly random points
+    Random,
+    /// One random point inside each cell of a grid over the pixel
+    #[default]
+    Stratified,
+    /// Points of the Halton sequence in bases 2 and 3 shifted by a random
+    /// amount in each pixel
+    Halton,
+}
+
+/// Source of the random numbers used in rendering one pixel. It is seeded by
+/// the pixel's position so that a render can be repeated exactly regardless
+/// of which thread renders the pixel. Each sample gets its own seed too, so
+/// that the samples come out the same also when taken over many passes.
+pub struct PixelSampler {
+    pub rng: StdRng,
+    /// Seed of the pixel that the seeds of the samples are made from
+    seed: u64,
+    pattern: SamplePattern,
+    /// Cells on each side of the grid of stratified samples
+    grid_size: usize,
+    /// The grid cells in the order they are sampled. The order is random so
+    /// that stopping early still covers the pixel evenly on average.
+    cells: Vec<usize>,
+    /// Random shift of the Halton points
+    shift: (f32, f32),
+}
+
+impl PixelSampler {
+    /// Sampler for the pixel at index `pixel` of the image, when `samples`
+    /// points are taken inside it.
+    pub fn new(seed: u64, pixel: usize, pattern: SamplePattern, samples: usize) -> Self {
+        let seed = mix(seed, pixel as u64);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let shift = (rng.gen::<f32>(), rng.gen::<f32>());
+        let grid_size = (samples as f32).sqrt() as usize;
+        let cells = match pattern {
+            SamplePattern::Stratified => {
+                let mut cells: Vec<usize> = (0..grid_size * grid_size).collect();
+                cells.shuffle(&mut rng);
+                cells
+            }
+            _ => Vec::new(),
+        };
+        PixelSampler {
+            rng,
+            seed,
+            pattern,
+            grid_size,
+            cells,
+            shift,
+        }
+    }
+
+    /// Return the position of the `i`th sample inside the pixel with both
+    /// coordinates in [0, 1). Also reseeds `rng` for the rest of the sample.
+    pub fn pixel_offset(&mut self, i: usize) -> (f32, f32) {
+        self.rng = StdRng::seed_from_u64(mix(self.seed, i as u64 + 1));
+        match self.pattern {
+            SamplePattern::Stratified if i < self.cells.len() => {
+                let cell = self.cells[i];
+                let (col, row) = (cell % self.grid_size, cell / self.grid_size);
+                let cell_size = 1.0 / self.grid_size as f32;
+                (
+                    (col as f32 + self.rng.gen::<f32>()) * cell_size,
+                    (row as f32 + self.rng.gen::<f32>()) * cell_size,
+                )
+            }
+            SamplePattern::Halton => (
+                (radical_inverse(2, i + 1) + self.shift.0).fract(),
+                (radical_inverse(3, i + 1) + self.shift.1).fract(),
+            ),
+            // Samples left over from the grid are spread randomly
+            _ => (self.rng.gen::<f32>(), self.rng.gen::<f32>()),
+        }
+    }
+}
+
+/// Combine `value` into `seed` so that nearby values give unrelated seeds.
+fn mix(seed: u64, value: u64) -> u64 {
+    seed.wrapping_mul(0x9e37_79b9_7f4a_7c15).wrapping_add(value)
+}
+
+/// Mirror the digits of `i` in `base` around the decimal point, eg. 6 is 110
+/// in base 2 and turns into 0.011.
+fn radical_inverse(base: usize, mut i: usize) -> f32 {
+    let mut result = 0.0;
+    let mut digit_weight = 1.0 / base as f32;
+    while i > 0 {
+        result += (i % base) as f32 * digit_weight;
+        i /= base;
+        digit_weight /= base as f32;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_radical_inverse() {
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_stratified_covers_grid() {
+        let mut sampler = PixelSampler::new(0, 0, SamplePattern::Stratified, 9);
+        let mut cells = [false; 9];
+        for i in 0..9 {
+            let (x, y) = sampler.pixel_offset(i);
+            cells[(y * 3.0) as usize * 3 + (x * 3.0) as usize] = true;
+        }
+        assert!(cells.iter().all(|&x| x));
+    }
+
+    #[test]
+    fn test_same_seed_same_samples() {
+        for &pattern in &[
+            SamplePattern::Random,
+            SamplePattern::Stratified,
+            SamplePattern::Halton,
+        ] {
+            let mut a = PixelSampler::new(7, 42, pattern, 16);
+            let mut b = PixelSampler::new(7, 42, pattern, 16);
+            for i in 0..16 {
+                assert_eq!(a.pixel_offset(i), b.pixel_offset(i));
+            }
+            assert_eq!(a.rng.gen::<u64>(), b.rng.gen::<u64>());
+        }
+    }
+
+    #[test]
+    fn test_sample_independent_of_earlier_samples() {
+        let mut a = PixelSampler::new(7, 42, SamplePattern::Random, 16);
+        let mut b = PixelSampler::new(7, 42, SamplePattern::Random, 16);
+        for i in 0..4 {
+            a.pixel_offset(i);
+            a.rng.gen::<u64>();
+        }
+        assert_eq!(a.pixel_offset(4), b.pixel_offset(4));
+        assert_eq!(a.rng.gen::<u64>(), b.rng.gen::<u64>());
+    }
+}
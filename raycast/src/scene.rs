@@ -1,21 +1,46 @@
 use std::collections;
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
+use std::f32::consts::PI;
 use std::fs;
 use std::io::Read;
 use std::path;
 
+use rand::RngCore;
 use serde_json::{from_value, Error as SerdeError, Value as SerdeValue};
 
-use crate::{color::Color, objects, ray::Ray, vector::Vector3, Intersect, Intersection, Light};
+use crate::{
+    bounds::BoundingBox,
+    camera::CameraDescription,
+    color::{self, Color},
+    environment::{Environment, EnvironmentDescription},
+    light::Light,
+    objects,
+    ray::Ray,
+    raycaster::RenderSettings,
+    sky::{Sky, SkyDescription},
+    transform::Transform,
+    vector::Vector3,
+    Intersect, Intersection,
+};
 
 /// A collection of things used in rendering a scene
 pub struct Scene {
     pub ambient_color: Color,
-    pub fov: f32,
+    pub camera: CameraDescription,
+    /// Alternative views of the scene by name, in alphabetical order
+    pub cameras: collections::BTreeMap<String, CameraDescription>,
+    pub render: RenderSettings,
     lights: Vec<Light>,
+    background: Option<Background>,
     objects: Vec<objects::Object3D>,
 }
 
+/// What is seen in the directions where rays escape the scene
+enum Background {
+    Environment(Environment),
+    Sky(Sky),
+}
+
 impl Scene {
     pub fn from_file(source_path: &path::Path) -> Result<Self, Box<dyn std::error::Error>> {
         // Load view from file
@@ -26,16 +51,36 @@ impl Scene {
 
         let mut json: serde_json::Value = serde_json::from_str(&contents)?;
 
+        // Look for the environment map next to the scene file
+        if let (Some(SerdeValue::String(map_path)), Some(scene_dir)) =
+            (json.pointer_mut("/environment/path"), source_path.parent())
+        {
+            *map_path = scene_dir.join(&map_path).to_string_lossy().into_owned();
+        }
+
         Self::try_from(&mut json).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
     }
 
-    /// Recursive function that traces the ray `n` times
-    pub fn trace(&self, ray: &Ray, n: usize) -> Color {
+    /// Recursive function that traces the ray `n` times. Random numbers for
+    /// sampling the lights are taken from `rng`.
+    pub fn trace(&self, ray: &Ray, n: usize, rng: &mut dyn RngCore) -> Color {
+        // Every ray spawned from this one is shot at the same moment, so the
+        // moving objects are placed only once
+        self.trace_placed(ray, n, rng, &self.placements_at(ray.time))
+    }
+
+    fn trace_placed(
+        &self,
+        ray: &Ray,
+        n: usize,
+        rng: &mut dyn RngCore,
+        placements: &[objects::Placement],
+    ) -> Color {
         // Shade with ambient color each time
         let mut color = self.ambient_color;
         if n > 0 {
             // TODO is epsilon needed here?
-            if let Some(intr) = self.intersect(ray, f32::EPSILON) {
+            if let Some((_, intr)) = self.hit_placed(ray, f32::EPSILON, placements) {
                 // Nudge off of the surface so that ray does not re-collide
                 // (see "shadow acne")
                 // NOTE The "bias" (ie. normal * epsilon) seems hard to get
@@ -43,60 +88,175 @@ impl Scene {
                 let off_surface = intr.point + (intr.normal * 0.0001);
 
                 for light in &self.lights {
-                    let (light_distance, towards_light) = {
-                        let v = light.position - intr.point;
-                        (v.length(), v.normalized())
-                    };
-
-                    // Shadows:
-                    let shadow_ray = Ray {
-                        origin: off_surface,
-                        direction: towards_light,
-                    };
-
-                    // If shadow ray does not cast shadow, color the point
-                    if self.intersect(&shadow_ray, f32::EPSILON).is_none() {
-                        // Shading model from:
-                        // http://www.cs.cornell.edu/courses/cs4620/2014fa/lectures/05rt-shading.pdf
-                        let intensity = light.intensity / light_distance;
-                        let bisector = {
-                            let v: Vector3 = (-intr.incoming).into();
-                            let w: Vector3 = towards_light.into();
-                            (v + w).normalized()
+                    // Area lights are averaged over many points on them
+                    let sample_points = light.sample_points(intr.point, rng);
+                    let sample_weight = 1.0 / sample_points.len() as f32;
+
+                    for sample_point in sample_points {
+                        let (light_distance, towards_light) = {
+                            let v = sample_point - intr.point;
+                            (v.length(), v.normalized())
+                        };
+
+                        // Eg. spot lights only shine inside their cone
+                        let emission_factor = light.emission_factor(&towards_light);
+                        if emission_factor <= 0.0 {
+                            continue;
+                        }
+
+                        // Shadows:
+                        let shadow_ray = Ray {
+                            origin: off_surface,
+                            direction: towards_light,
+                            time: ray.time,
                         };
 
-                        let d = intr.normal.dot(&towards_light);
-                        if d >= 0.0 {
-                            let s = intr.normal.dot(&bisector);
-                            color += &(
-                                // Diffuse
-                                intr.material.color
-                                * intensity
-                                * d
-                                // Specular
-                                + light.color
-                                * intensity
-                                * f32::max(0.0, s)
-                                   .powi(intr.material.shininess)
-                            );
+                        // If shadow ray does not cast shadow, color the point
+                        if !self.is_occluded(&shadow_ray, light_distance, placements) {
+                            // Shading model from:
+                            // http://www.cs.cornell.edu/courses/cs4620/2014fa/lectures/05rt-shading.pdf
+                            let intensity = light.intensity
+                                * light.falloff.attenuation(light_distance)
+                                * emission_factor
+                                * sample_weight;
+                            let bisector = {
+                                let v: Vector3 = (-intr.incoming).into();
+                                let w: Vector3 = towards_light.into();
+                                (v + w).normalized()
+                            };
+
+                            let d = intr.normal.dot(&towards_light);
+                            if d >= 0.0 {
+                                let s = intr.normal.dot(&bisector);
+                                color += &(
+                                    // Diffuse
+                                    intr.material.color
+                                    * intensity
+                                    * d
+                                    // Specular
+                                    + light.color
+                                    * intensity
+                                    * f32::max(0.0, s)
+                                       .powi(intr.material.shininess)
+                                );
+                            }
                         }
                     }
                 }
 
+                // Light from the environment map: Sample directions towards
+                // its bright areas and weigh each by the probability of
+                // having picked it
+                if let Some(Background::Environment(environment)) = &self.background {
+                    let mut environment_color = color::consts::BLACK;
+                    for _ in 0..environment.samples {
+                        if let Some((direction, radiance, pdf)) = environment.sample(rng) {
+                            let d = intr.normal.dot(&direction);
+                            let shadow_ray = Ray {
+                                origin: off_surface,
+                                direction,
+                                time: ray.time,
+                            };
+                            if d > 0.0
+                                && self
+                                    .hit_placed(&shadow_ray, f32::EPSILON, placements)
+                                    .is_none()
+                            {
+                                // Lambertian surface reflects color / PI
+                                environment_color += &(radiance * (d / (pdf * PI)));
+                            }
+                        }
+                    }
+                    color += &(intr.material.color
+                        * environment_color
+                        * (1.0 / environment.samples.max(1) as f32));
+                }
+
                 // Reflections: Add color seen by reflected ray to current ray
                 let reflected_ray = Ray {
                     origin: off_surface,
                     direction: intr.incoming.reflect(&intr.normal),
+                    time: ray.time,
                 };
 
                 // Recursive call TODO Add attenuation from reflection
-                return color + self.trace(&reflected_ray, n - 1);
+                return color + self.trace_placed(&reflected_ray, n - 1, rng, placements);
             }
+            // The ray escapes the scene
+            return self.background(ray, color);
         }
-        // End recursion:
+        // End recursion: The ray is not traced further so it is not known
+        // what it would see
         color
     }
 
+    /// Return the color seen by a ray that escapes the scene: the environment
+    /// map or sky if there is one and otherwise `ambient`.
+    fn background(&self, ray: &Ray, ambient: Color) -> Color {
+        match &self.background {
+            Some(Background::Environment(environment)) => environment.color(&ray.direction),
+            Some(Background::Sky(sky)) => sky.color(&ray.direction),
+            None => ambient,
+        }
+    }
+
+    /// Check if something blocks the ray before it has travelled `distance`
+    fn is_occluded(&self, ray: &Ray, distance: f32, placements: &[objects::Placement]) -> bool {
+        self.objects.iter().zip(placements).any(|(x, placement)| {
+            // The distance of a hit is in the object's own space, which eg.
+            // scaling changes, so compare in world space
+            matches!(
+                x.intersect_placed(ray, f32::EPSILON, placement),
+                Some(intr) if (x.to_world(intr.point, ray.time) - ray.origin).length() < distance
+            )
+        })
+    }
+
+    /// Return where each object is at `time`.
+    fn placements_at(&self, time: f32) -> Vec<objects::Placement> {
+        self.objects.iter().map(|x| x.placement_at(time)).collect()
+    }
+
+    /// Return the closest intersection of the ray with the objects at
+    /// `placements` and the index of the object it is with.
+    fn hit_placed(
+        &self,
+        ray: &Ray,
+        tmin: f32,
+        placements: &[objects::Placement],
+    ) -> Option<(usize, Intersection)> {
+        self.objects
+            .iter()
+            .zip(placements)
+            .enumerate()
+            .filter_map(|(i, (x, placement))| {
+                x.intersect_placed(ray, tmin, placement)
+                    .map(|intr| (i, intr))
+            })
+            .reduce(|acc, x| if x.1.t < acc.1.t { x } else { acc })
+    }
+
+    /// Return a box containing every bounded object in the scene. Unbounded
+    /// objects like planes are left out.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        self.objects
+            .iter()
+            .filter_map(|x| x.bounding_box())
+            .reduce(|acc, x| acc.union(&x))
+    }
+
+    /// Return the first intersection of the ray and the index of the object
+    /// it is with. Unlike in tracing, the point and distance of the
+    /// intersection are in world space.
+    pub(crate) fn first_hit(&self, ray: &Ray) -> Option<(usize, Intersection)> {
+        self.hit_placed(ray, f32::EPSILON, &self.placements_at(ray.time))
+            .map(|(i, mut intr)| {
+                intr.point = self.objects[i].to_world(intr.point, ray.time);
+                intr.t = (intr.point - ray.origin).length();
+                (i, intr)
+            })
+    }
+
     pub fn color_debug(&self, ray: &Ray) -> Color {
         // TODO is epsilon needed here?
         if let Some(intr) = self.intersect(ray, f32::EPSILON) {
@@ -104,7 +264,7 @@ impl Scene {
             let Vector3 { x, y, z } = intr.normal.into();
             Color::new(x, y, z)
         } else {
-            self.ambient_color
+            self.background(ray, self.ambient_color)
         }
     }
 }
@@ -117,16 +277,46 @@ impl<'a> TryFrom<&'a mut SerdeValue> for Scene {
     fn try_from(json: &'a mut SerdeValue) -> Result<Self, SerdeError> {
         let ambient_color = from_value(json["ambient_color"].take())?;
 
-        // NOTE `fov` is turned into radians from the degrees in JSON
-        let fov = utils::degs_to_rads(from_value(json["fov"].take())?);
+        let cameras: collections::BTreeMap<String, CameraDescription> =
+            from_value::<Option<_>>(json["cameras"].take())?.unwrap_or_default();
+
+        // Older scenes only set the field of view for a camera at the
+        // default position. Scenes with only named cameras use the one whose
+        // name is first in alphabetical order, not the first one in the file
+        let camera = match (json["camera"].take(), json["fov"].take()) {
+            (SerdeValue::Null, SerdeValue::Null) if !cameras.is_empty() => {
+                cameras.values().next().unwrap().clone()
+            }
+            (SerdeValue::Null, fov) => CameraDescription::with_fov(from_value(fov)?),
+            (x, _) => from_value(x)?,
+        };
 
         // The scene is described in JSON with different intersectable objects
         // named by the user. Here those names are turned into indices into the
         // said objects ie. the actual objects are allocated once and in
         // rendering used multiple times with different transformations
 
+        let render =
+            from_value::<Option<RenderSettings>>(json["render"].take())?.unwrap_or_default();
+
         let lights: Vec<Light> = from_value(json["lights"].take())?;
 
+        let environment = from_value::<Option<EnvironmentDescription>>(json["environment"].take())?
+            .map(Environment::load)
+            .transpose()
+            .map_err(|e| <SerdeError as serde::de::Error>::custom(e.to_string()))?;
+        let sky = from_value::<Option<SkyDescription>>(json["sky"].take())?;
+        let background = match (environment, sky) {
+            (Some(_), Some(_)) => {
+                return Err(<SerdeError as serde::de::Error>::custom(
+                    "Only one of 'environment' and 'sky' can be used as the background",
+                ))
+            }
+            (Some(environment), None) => Some(Background::Environment(environment)),
+            (None, Some(sky)) => Some(Background::Sky(sky.into())),
+            (None, None) => None,
+        };
+
         // Helper for converting json into shapes choosing between object or
         // array. If string, then just pull from the named-map at upper level
         let shapes_from_json = |x: SerdeValue| {
@@ -159,18 +349,35 @@ impl<'a> TryFrom<&'a mut SerdeValue> for Scene {
         if let SerdeValue::Array(vec) = json["objects"].take() {
             objects.reserve(vec.len());
             for (i, mut value) in vec.into_iter().enumerate() {
-                // Parse transform matrix from string
-                let transform = from_value::<Option<String>>(value["transform"].take())?
-                    .as_ref()
-                    .map(|s| {
-                        s[..].try_into().unwrap_or_else(|_| {
-                            panic!(
-                                "Bad transform string on the {} item in \
-                                    'objects'",
-                                i
-                            )
+                // Parse transforms from strings
+                let parse_transform = |key: &str, value: SerdeValue| {
+                    from_value::<Option<String>>(value).map(|x| {
+                        x.map(|s| {
+                            Transform::try_from(&s[..]).unwrap_or_else(|_| {
+                                panic!(
+                                    "Bad {} string on the {} item in \
+                                        'objects'",
+                                    key, i
+                                )
+                            })
                         })
-                    });
+                    })
+                };
+                let transform = parse_transform("transform", value["transform"].take())?;
+
+                // Moving objects have a transform for when the shutter closes
+                // or move at a constant velocity while it is open
+                let transform_end =
+                    parse_transform("transform_end", value["transform_end"].take())?;
+                let velocity = from_value::<Option<Vector3>>(value["velocity"].take())?;
+                let motion = if transform_end.is_some() || velocity.is_some() {
+                    Some(objects::Motion {
+                        end: transform_end,
+                        velocity,
+                    })
+                } else {
+                    None
+                };
 
                 // Either create the raw object or choose from named ones
                 let object = {
@@ -201,7 +408,7 @@ impl<'a> TryFrom<&'a mut SerdeValue> for Scene {
 
                 let material = from_value(value["material"].take())?;
 
-                objects.push(objects::Object3D::new(transform, object, material));
+                objects.push(objects::Object3D::new(transform, motion, object, material));
             }
         } else {
             panic!("The key 'objects' does not match to an array")
@@ -209,8 +416,11 @@ impl<'a> TryFrom<&'a mut SerdeValue> for Scene {
 
         Ok(Scene {
             ambient_color,
-            fov,
+            camera,
+            cameras,
+            render,
             lights,
+            background,
             objects,
         })
     }
@@ -219,9 +429,113 @@ impl<'a> TryFrom<&'a mut SerdeValue> for Scene {
 impl Intersect for Scene {
     fn intersect(&self, ray: &Ray, tmin: f32) -> Option<Intersection> {
         //TODO intersect lights? (simulate a lens as glass sphere over camera)
-        self.objects
-            .iter()
-            .filter_map(|x| x.intersect(ray, tmin))
-            .reduce(|acc, x| if x.t < acc.t { x } else { acc })
+        self.hit_placed(ray, tmin, &self.placements_at(ray.time))
+            .map(|(_, intr)| intr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_object_occludes() {
+        let scene = Scene::try_from(&mut serde_json::json!({
+            "fov": 90,
+            "ambient_color": [0, 0, 0],
+            "lights": [],
+            "named": {},
+            "objects": [
+                {
+                    "transform": "Translate 0 5 0; Scale 0.1",
+                    "object": { "Sphere": { "origin": [0, 0, 0], "radius": 10 } }
+                }
+            ]
+        }))
+        .unwrap();
+        let ray = Ray {
+            origin: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            }
+            .normalized(),
+            time: 0.0,
+        };
+        let placements = scene.placements_at(ray.time);
+
+        // The sphere has a radius of 1 around y = 5 in world space
+        assert!(scene.is_occluded(&ray, 10.0, &placements));
+        assert!(!scene.is_occluded(&ray, 3.0, &placements));
+    }
+
+    #[test]
+    fn test_named_cameras() {
+        let scene = |camera: serde_json::Value| {
+            let mut json = serde_json::json!({
+                "ambient_color": [0, 0, 0],
+                "cameras": {
+                    "side": { "position": [5, 0, 0] },
+                    "front": { "position": [0, 0, 5], "fov": 40 }
+                },
+                "lights": [],
+                "named": {},
+                "objects": []
+            });
+            json["camera"] = camera;
+            Scene::try_from(&mut json).unwrap()
+        };
+
+        let fallback = scene(serde_json::Value::Null);
+        assert_eq!(
+            fallback.cameras.keys().collect::<Vec<_>>(),
+            vec!["front", "side"]
+        );
+        assert!((fallback.cameras["side"].position.x - 5.0).abs() < 1e-6);
+        // Without a camera the alphabetically first of the named ones is used
+        assert!((fallback.camera.position.z - 5.0).abs() < 1e-6);
+        assert!((fallback.camera.fov - 40.0).abs() < 1e-6);
+
+        let own = scene(
+            serde_json::json!({ "position": [0, 3, 0], "look_at": [0, 0, 0], "up": [0, 0, 1] }),
+        );
+        assert!((own.camera.position.y - 3.0).abs() < 1e-6);
+        assert_eq!(own.cameras.len(), 2);
+    }
+
+    #[test]
+    fn test_background_only_where_rays_escape() {
+        let scene = Scene::try_from(&mut serde_json::json!({
+            "fov": 90,
+            "ambient_color": [0, 0, 0],
+            "sky": { "Gradient": { "horizon": [255, 255, 255], "zenith": [255, 255, 255] } },
+            "lights": [],
+            "named": {},
+            "objects": []
+        }))
+        .unwrap();
+        let ray = Ray {
+            origin: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            direction: Vector3 {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            }
+            .normalized(),
+            time: 0.0,
+        };
+        let rng = &mut rand::thread_rng();
+        assert!(scene.trace(&ray, 1, rng).luminance() > 0.9);
+        // Without tracing the ray it is unknown whether it would escape
+        assert_eq!(scene.trace(&ray, 0, rng).luminance(), 0.0);
     }
 }
@@ -0,0 +1,245 @@
+use crate::{
+    color::{self, Color},
+    vector::UnitVector3,
+};
+
+/// The `sky` section of a scene file.
+#[derive(serde::Deserialize)]
+pub enum SkyDescription {
+    /// Blend from `horizon` to `zenith` when looking upwards.
+    Gradient {
+        horizon: Color,
+        zenith: Color,
+        #[serde(default)]
+        ground: Option<Color>,
+    },
+    /// Clear daylight sky with the sun towards `sun_direction`.
+    Preetham {
+        sun_direction: UnitVector3,
+        /// Haziness of the air from 2 (very clear) to about 10 (hazy)
+        #[serde(default = "default_turbidity")]
+        turbidity: f32,
+        /// Multiplier for the sky luminance, which is in kcd/m^2
+        #[serde(default = "default_intensity")]
+        intensity: f32,
+        #[serde(default)]
+        ground: Option<Color>,
+    },
+}
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+fn default_intensity() -> f32 {
+    0.1
+}
+
+/// Color seen below the horizon if the scene does not say otherwise.
+const DEFAULT_GROUND: Color = Color::new(0.1, 0.1, 0.1);
+
+/// A procedural background that can be evaluated in any direction.
+pub enum Sky {
+    Gradient {
+        horizon: Color,
+        zenith: Color,
+        ground: Color,
+    },
+    Preetham(Preetham),
+}
+
+impl From<SkyDescription> for Sky {
+    fn from(description: SkyDescription) -> Self {
+        match description {
+            SkyDescription::Gradient {
+                horizon,
+                zenith,
+                ground,
+            } => Sky::Gradient {
+                horizon,
+                zenith,
+                ground: ground.unwrap_or(DEFAULT_GROUND),
+            },
+            SkyDescription::Preetham {
+                sun_direction,
+                turbidity,
+                intensity,
+                ground,
+            } => Sky::Preetham(Preetham::new(
+                sun_direction,
+                turbidity,
+                intensity,
+                ground.unwrap_or(DEFAULT_GROUND),
+            )),
+        }
+    }
+}
+
+impl Sky {
+    /// Return the color seen in `direction`.
+    pub fn color(&self, direction: &UnitVector3) -> Color {
+        match self {
+            Sky::Gradient {
+                horizon,
+                zenith,
+                ground,
+            } => {
+                let t = direction.y();
+                if t < 0.0 {
+                    *ground
+                } else {
+                    *horizon * (1.0 - t) + *zenith * t
+                }
+            }
+            Sky::Preetham(sky) => sky.color(direction),
+        }
+    }
+}
+
+/// The analytic daylight model from "A Practical Analytic Model for Daylight"
+/// by Preetham, Shirley and Smits (1999).
+pub struct Preetham {
+    sun_direction: UnitVector3,
+    intensity: f32,
+    ground: Color,
+    /// Perez function coefficients A to E for luminance Y and chromaticities
+    /// x and y
+    perez: [[f32; 5]; 3],
+    /// The values of Y, x and y at zenith divided by the value of the Perez
+    /// function there
+    zenith: [f32; 3],
+}
+
+impl Preetham {
+    fn new(sun_direction: UnitVector3, turbidity: f32, intensity: f32, ground: Color) -> Self {
+        let t = turbidity;
+        // Keep the sun at most at the horizon to keep the formulas stable
+        let theta_s = sun_direction.y().clamp(0.0, 1.0).acos();
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let thetas = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+        let chromaticity = |m: [[f32; 4]; 3]| {
+            let row = |r: [f32; 4]| r.iter().zip(thetas.iter()).map(|(a, b)| a * b).sum::<f32>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        let mut zenith = [zenith_luminance, zenith_x, zenith_y];
+        for (z, coefficients) in zenith.iter_mut().zip(perez.iter()) {
+            *z /= perez_function(coefficients, 0.0, theta_s);
+        }
+
+        Preetham {
+            sun_direction,
+            intensity,
+            ground,
+            perez,
+            zenith,
+        }
+    }
+
+    fn color(&self, direction: &UnitVector3) -> Color {
+        if direction.y() < 0.0 {
+            return self.ground;
+        }
+        // Stay a bit above the horizon where the model blows up
+        let theta = direction.y().max(0.001).acos();
+        let gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let [luminance, x, y] = {
+            let mut values = self.zenith;
+            for (v, coefficients) in values.iter_mut().zip(self.perez.iter()) {
+                *v *= perez_function(coefficients, theta, gamma);
+            }
+            values
+        };
+
+        xyy_to_rgb(x, y, luminance * self.intensity)
+    }
+}
+
+/// Distribution of sky luminance from "An all-weather model for sky luminance
+/// distribution" by Perez et al.
+fn perez_function(coefficients: &[f32; 5], theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    (1.0 + a * (b / theta.cos()).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+/// Convert CIE xyY into linear sRGB.
+pub(crate) fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Color {
+    if y <= 0.0 {
+        return color::consts::BLACK;
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Color::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    fn direction(x: f32, y: f32, z: f32) -> UnitVector3 {
+        Vector3 { x, y, z }.normalized()
+    }
+
+    #[test]
+    fn test_preetham_zenith_luminance() {
+        // Looking straight up gives back the zenith luminance of the model
+        let sky = Preetham::new(direction(0.0, 1.0, -1.0), 3.0, 1.0, DEFAULT_GROUND);
+        let chi = (4.0 / 9.0 - 3.0 / 120.0) * (std::f32::consts::PI / 2.0);
+        let expected = (4.0453 * 3.0 - 4.9710) * chi.tan() - 0.2155 * 3.0 + 2.4192;
+        let luminance = sky.color(&direction(0.0, 1.0, 0.0)).luminance();
+        assert!((luminance - expected).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_preetham_brighter_towards_sun() {
+        let sun = direction(1.0, 0.5, 0.0);
+        let sky = Preetham::new(sun, 3.0, 1.0, DEFAULT_GROUND);
+        let near_sun = sky.color(&direction(1.0, 0.55, 0.0)).luminance();
+        let away_from_sun = sky.color(&direction(-1.0, 0.55, 0.0)).luminance();
+        assert!(near_sun > away_from_sun);
+        assert!(away_from_sun > 0.0);
+    }
+}
@@ -0,0 +1,187 @@
+use image::{ImageBuffer, Primitive, Rgb};
+
+use crate::camera::{self, CameraDescription};
+
+/// The `stereo` part of a camera section that makes the camera render a view
+/// for each eye.
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+pub struct Stereo {
+    /// Distance between the eyes in scene units
+    #[serde(default = "default_interocular_distance")]
+    pub interocular_distance: f32,
+    /// Distance from the camera to where the views of the eyes line up.
+    /// Things at this distance appear at the depth of the screen. By default
+    /// the distance to `look_at`.
+    #[serde(default)]
+    pub convergence: Option<f32>,
+    #[serde(default)]
+    pub layout: Layout,
+}
+
+fn default_interocular_distance() -> f32 {
+    0.065
+}
+
+/// Select how the views of the two eyes are put into one image.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub enum Layout {
+    /// Left eye on the left half and right eye on the right half.
+    #[default]
+    SideBySide,
+    /// Left eye on the top half and right eye on the bottom half.
+    OverUnder,
+    /// Red channel from the left eye and green and blue from the right eye,
+    /// for viewing with red/cyan glasses.
+    Anaglyph,
+}
+
+impl Stereo {
+    /// Return the camera descriptions for the left and right eye. The eyes
+    /// look in the same direction as the camera and their image planes are
+    /// shifted towards each other to line up at the convergence distance.
+    /// Turning the eyes inwards instead would tilt their image planes apart
+    /// and distort the views differently at the top and bottom.
+    ///
+    /// # Panics:
+    /// This panics if the camera does not have a viewing direction and a
+    /// horizontal direction (see `camera::basis`).
+    pub fn eyes(&self, description: &CameraDescription) -> (CameraDescription, CameraDescription) {
+        let (horizontal, _, _) =
+            camera::basis(description.position, description.look_at, description.up);
+        let convergence = self
+            .convergence
+            .unwrap_or_else(|| (description.look_at - description.position).length());
+        let dist_to_image = 1.0 / (utils::degs_to_rads(description.fov) / 2.0).tan();
+        // Point at the convergence distance straight ahead of the camera is
+        // half the interocular distance to the side of each eye
+        let shift = self.interocular_distance / 2.0 * dist_to_image / convergence;
+
+        let eye = |side: f32| {
+            let offset = horizontal * (side * self.interocular_distance / 2.0);
+            CameraDescription {
+                position: description.position + offset,
+                look_at: description.look_at + offset,
+                shift: description.shift - side * shift,
+                stereo: None,
+                ..description.clone()
+            }
+        };
+        (eye(-1.0), eye(1.0))
+    }
+
+    /// Combine the renders of the left and right eye into one image.
+    ///
+    /// # Panics:
+    /// This panics if the images are not the same size.
+    pub fn composite<T: Primitive + 'static>(
+        &self,
+        left: &ImageBuffer<Rgb<T>, Vec<T>>,
+        right: &ImageBuffer<Rgb<T>, Vec<T>>,
+    ) -> ImageBuffer<Rgb<T>, Vec<T>> {
+        assert_eq!(left.dimensions(), right.dimensions());
+        let (width, height) = left.dimensions();
+        match self.layout {
+            Layout::SideBySide => ImageBuffer::from_fn(2 * width, height, |x, y| {
+                if x < width {
+                    *left.get_pixel(x, y)
+                } else {
+                    *right.get_pixel(x - width, y)
+                }
+            }),
+            Layout::OverUnder => ImageBuffer::from_fn(width, 2 * height, |x, y| {
+                if y < height {
+                    *left.get_pixel(x, y)
+                } else {
+                    *right.get_pixel(x, y - height)
+                }
+            }),
+            Layout::Anaglyph => ImageBuffer::from_fn(width, height, |x, y| {
+                let Rgb([r, _, _]) = *left.get_pixel(x, y);
+                let Rgb([_, g, b]) = *right.get_pixel(x, y);
+                Rgb([r, g, b])
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{camera::Camera, ray::Ray};
+    use image::RgbImage;
+
+    fn stereo(convergence: Option<f32>, layout: Layout) -> Stereo {
+        Stereo {
+            interocular_distance: 0.2,
+            convergence,
+            layout,
+        }
+    }
+
+    #[test]
+    fn test_eyes_are_apart_and_converge() {
+        let description = CameraDescription::with_fov(60.0);
+        let (left, right) = stereo(Some(4.0), Layout::SideBySide).eyes(&description);
+
+        // The eyes are on either side of the camera, left one on the left
+        assert!((left.position.x - -0.1).abs() < 1e-6);
+        assert!((right.position.x - 0.1).abs() < 1e-6);
+        assert!(left.position.y.abs() < 1e-6 && (left.position.z - 1.0).abs() < 1e-6);
+
+        // Both look straight ahead like the camera
+        for eye in &[&left, &right] {
+            let direction = eye.look_at - eye.position;
+            assert!(direction.x.abs() < 1e-6 && direction.y.abs() < 1e-6);
+            assert!(eye.stereo.is_none());
+        }
+
+        // Anywhere in the image the eyes see the same point at the
+        // convergence distance, so there is no vertical parallax
+        let rng = &mut rand::thread_rng();
+        let left = camera::PerspectiveCamera::from_description(&left, 4, 4);
+        let right = camera::PerspectiveCamera::from_description(&right, 4, 4);
+        for &(x, y) in &[(0.0, 0.0), (0.7, 0.9), (-0.8, -0.5)] {
+            let hit = |ray: Ray| ray.cast((-3.0 - ray.origin.z) / ray.direction.z());
+            let l = hit(left.shoot_at(x, y, rng).unwrap());
+            let r = hit(right.shoot_at(x, y, rng).unwrap());
+            assert!((l - r).length() < 1e-5, "{:?} and {:?}", l, r);
+            if x == 0.0 && y == 0.0 {
+                assert!(l.x.abs() < 1e-5 && l.y.abs() < 1e-5);
+            }
+        }
+
+        // By default the eyes converge at `look_at`
+        let (left, right) = stereo(None, Layout::SideBySide).eyes(&description);
+        let left = camera::PerspectiveCamera::from_description(&left, 4, 4);
+        let right = camera::PerspectiveCamera::from_description(&right, 4, 4);
+        let hit = |ray: Ray| ray.cast(-ray.origin.z / ray.direction.z());
+        let l = hit(left.shoot_at(0.5, 0.5, rng).unwrap());
+        let r = hit(right.shoot_at(0.5, 0.5, rng).unwrap());
+        assert!((l - r).length() < 1e-5);
+    }
+
+    #[test]
+    fn test_composite_dimensions() {
+        let left = RgbImage::from_pixel(4, 3, Rgb([255, 0, 0]));
+        let right = RgbImage::from_pixel(4, 3, Rgb([0, 0, 255]));
+
+        let side_by_side = stereo(None, Layout::SideBySide).composite(&left, &right);
+        assert_eq!(side_by_side.dimensions(), (8, 3));
+        assert_eq!(*side_by_side.get_pixel(3, 0), Rgb([255, 0, 0]));
+        assert_eq!(*side_by_side.get_pixel(4, 0), Rgb([0, 0, 255]));
+
+        let over_under = stereo(None, Layout::OverUnder).composite(&left, &right);
+        assert_eq!(over_under.dimensions(), (4, 6));
+        assert_eq!(*over_under.get_pixel(0, 2), Rgb([255, 0, 0]));
+        assert_eq!(*over_under.get_pixel(0, 3), Rgb([0, 0, 255]));
+    }
+
+    #[test]
+    fn test_anaglyph_channels() {
+        let left = RgbImage::from_pixel(2, 2, Rgb([10, 20, 30]));
+        let right = RgbImage::from_pixel(2, 2, Rgb([40, 50, 60]));
+        let anaglyph = stereo(None, Layout::Anaglyph).composite(&left, &right);
+        assert_eq!(anaglyph.dimensions(), (2, 2));
+        assert!(anaglyph.pixels().all(|p| *p == Rgb([10, 50, 60])));
+    }
+}
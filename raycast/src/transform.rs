@@ -0,0 +1,185 @@
+use std::convert::TryFrom;
+
+use crate::{matrix::SquareMatrix4, vector::Vector4};
+
+/// One step of a transformation as written in a scene file. Rotations are in
+/// radians.
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    Translate(Vector4),
+    RotX(f32),
+    RotY(f32),
+    RotZ(f32),
+    Scale(Vector4),
+}
+
+impl Step {
+    fn matrix(&self) -> SquareMatrix4 {
+        match *self {
+            Step::Translate(v) => SquareMatrix4::translation(v),
+            Step::RotX(rads) => SquareMatrix4::rot_x(rads),
+            Step::RotY(rads) => SquareMatrix4::rot_y(rads),
+            Step::RotZ(rads) => SquareMatrix4::rot_z(rads),
+            Step::Scale(v) => SquareMatrix4::scale(v),
+        }
+    }
+
+    /// Blend the parameters of two steps of the same kind.
+    fn lerp(&self, other: &Step, t: f32) -> Option<Step> {
+        let f = |a: f32, b: f32| a + (b - a) * t;
+        let v = |a: Vector4, b: Vector4| Vector4 {
+            x: f(a.x, b.x),
+            y: f(a.y, b.y),
+            z: f(a.z, b.z),
+            w: f(a.w, b.w),
+        };
+        Some(match (*self, *other) {
+            (Step::Translate(a), Step::Translate(b)) => Step::Translate(v(a, b)),
+            (Step::RotX(a), Step::RotX(b)) => Step::RotX(f(a, b)),
+            (Step::RotY(a), Step::RotY(b)) => Step::RotY(f(a, b)),
+            (Step::RotZ(a), Step::RotZ(b)) => Step::RotZ(f(a, b)),
+            (Step::Scale(a), Step::Scale(b)) => Step::Scale(v(a, b)),
+            _ => return None,
+        })
+    }
+}
+
+/// A sequence of translations, rotations and scalings parsed from a string
+/// like `"RotY 45; Translate 0 1 -2"`.
+#[derive(Clone, Debug, Default)]
+pub struct Transform(Vec<Step>);
+
+impl Transform {
+    /// Combine the steps into a single matrix.
+    pub fn matrix(&self) -> SquareMatrix4 {
+        self.0
+            .iter()
+            .fold(SquareMatrix4::identity(), |acc, x| &acc * &x.matrix())
+    }
+
+    /// Return the matrix `t` of the way from `self` to `other`, where `t` is in
+    /// [0, 1]. If both are made of the same kinds of steps, the parameters of
+    /// the steps are blended so that eg. a rotation from 0 to 360 degrees turns
+    /// a full circle. Otherwise the matrices themselves are blended.
+    pub fn lerp(&self, other: &Transform, t: f32) -> SquareMatrix4 {
+        if self.0.len() == other.0.len() {
+            let steps: Option<Vec<Step>> = self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .map(|(a, b)| a.lerp(b, t))
+                .collect();
+            if let Some(steps) = steps {
+                return Transform(steps).matrix();
+            }
+        }
+
+        let (a, b) = (self.matrix(), other.matrix());
+        let mut m = SquareMatrix4::identity();
+        for (i, row) in m.0.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = a.0[i][j] + (b.0[i][j] - a.0[i][j]) * t;
+            }
+        }
+        m
+    }
+}
+
+// TODO It isn't exactly good to parse a matrix from some invented language
+impl TryFrom<&str> for Transform {
+    type Error = String; // TODO ParseError
+
+    fn try_from(parse_string: &str) -> Result<Self, Self::Error> {
+        let steps = parse_string
+            .trim()
+            .split_terminator(';')
+            .map(|s| {
+                let s = s.trim();
+                if s.starts_with("Translate") {
+                    let v: Vec<f32> = s[s.find('e').unwrap() + 1..]
+                        .split_ascii_whitespace()
+                        .map(|q| q.trim().parse::<f32>().unwrap()) //TODO
+                        .collect();
+                    Step::Translate(Vector4 {
+                        x: v[0],
+                        y: v[1],
+                        z: v[2],
+                        w: 1.0,
+                    })
+                } else if s.starts_with("RotX") {
+                    let rads = utils::degs_to_rads(
+                        s[s.find('X').unwrap() + 1..].trim().parse::<f32>().unwrap(),
+                    ); //TODO
+                    Step::RotX(rads)
+                } else if s.starts_with("RotY") {
+                    let rads = utils::degs_to_rads(
+                        s[s.find('Y').unwrap() + 1..].trim().parse::<f32>().unwrap(),
+                    ); //TODO
+                    Step::RotY(rads)
+                } else if s.starts_with("RotZ") {
+                    let rads = utils::degs_to_rads(
+                        s[s.find('Z').unwrap() + 1..].trim().parse::<f32>().unwrap(),
+                    ); //TODO
+                    Step::RotZ(rads)
+                } else if s.starts_with("Scale") {
+                    let v: Vec<f32> = s[s.find('e').unwrap() + 1..]
+                        .split_ascii_whitespace()
+                        .map(|q| q.trim().parse::<f32>().unwrap()) // TODO
+                        .collect();
+                    if let [a] = v[0..] {
+                        // Scale all 3 dimensions the same
+                        Step::Scale(Vector4 {
+                            x: a,
+                            y: a,
+                            z: a,
+                            w: a,
+                        })
+                    } else if let [x, y, z] = v[0..] {
+                        // Scale each differently
+                        Step::Scale(Vector4 { x, y, z, w: 1.0 })
+                    } else {
+                        // TODO return Err
+                        panic!("Insufficient number of scaling values")
+                    }
+                } else {
+                    // TODO return Err
+                    panic!("Transformation not found '{}'", s)
+                }
+            })
+            .collect();
+
+        Ok(Transform(steps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Transform {
+        Transform::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_lerp_endpoints() {
+        let (a, b) = (parse("Translate 0 0 0"), parse("Translate 2 4 -6"));
+        assert_eq!(a.lerp(&b, 0.0), a.matrix());
+        assert_eq!(a.lerp(&b, 1.0), b.matrix());
+        assert_eq!(a.lerp(&b, 0.5), parse("Translate 1 2 -3").matrix());
+    }
+
+    #[test]
+    fn test_lerp_full_turn() {
+        // Blending the angles goes around instead of staying in place like
+        // blending the (equal) matrices would
+        let (a, b) = (parse("RotY 0"), parse("RotY 360"));
+        assert_eq!(a.lerp(&b, 0.25), parse("RotY 90").matrix());
+    }
+
+    #[test]
+    fn test_lerp_different_steps() {
+        let (a, b) = (parse("Translate 0 0 0"), parse("Scale 3 3 3"));
+        let m = a.lerp(&b, 0.5);
+        assert_eq!(m, parse("Scale 2 2 2").matrix());
+    }
+}
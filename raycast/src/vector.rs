@@ -110,6 +110,28 @@ impl UnitVector3 {
     pub fn cross(&self, other: &Self) -> Self {
         self.0.cross(&other.0).normalized()
     }
+
+    /// Return two unit vectors that together with `self` form an orthonormal
+    /// basis.
+    pub fn orthonormal_basis(&self) -> (Self, Self) {
+        // Based on "Building an Orthonormal Basis, Revisited" by Duff et al.
+        let Vector3 { x, y, z } = self.0;
+        let sign = 1.0_f32.copysign(z);
+        let a = -1.0 / (sign + z);
+        let b = x * y * a;
+        (
+            UnitVector3(Vector3 {
+                x: 1.0 + sign * x * x * a,
+                y: sign * b,
+                z: -sign * x,
+            }),
+            UnitVector3(Vector3 {
+                x: b,
+                y: sign + y * y * a,
+                z: -y,
+            }),
+        )
+    }
 }
 
 impl<'de> serde::Deserialize<'de> for UnitVector3 {
@@ -160,6 +182,7 @@ impl TryFrom<Vector3> for UnitVector3 {
     }
 }
 
+#[derive(Copy, Clone, Debug)]
 pub struct Vector4 {
     pub x: f32,
     pub y: f32,